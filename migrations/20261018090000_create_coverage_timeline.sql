@@ -0,0 +1,9 @@
+CREATE TABLE coverage_timeline (
+    country_iso_alpha_3 VARCHAR(3) REFERENCES countries(iso_alpha_3),
+    category_name TEXT REFERENCES categories(name),
+    mode TEXT NOT NULL,
+    bucket_start timestamptz NOT NULL,
+    value DOUBLE PRECISION NOT NULL,
+    total BIGINT,
+    PRIMARY KEY (country_iso_alpha_3, category_name, mode, bucket_start)
+);
@@ -0,0 +1,7 @@
+CREATE TABLE tone_timeline (
+    country_iso_alpha_3 VARCHAR(3) REFERENCES countries(iso_alpha_3),
+    category_name TEXT REFERENCES categories(name),
+    bucket_start timestamptz NOT NULL,
+    average_tone DOUBLE PRECISION NOT NULL,
+    PRIMARY KEY (country_iso_alpha_3, category_name, bucket_start)
+);
@@ -0,0 +1,3 @@
+ALTER TABLE news_articles
+    ADD COLUMN url_mobile TEXT,
+    ADD COLUMN social_image TEXT;
@@ -0,0 +1,6 @@
+CREATE TABLE category_keywords (
+    category_name TEXT NOT NULL REFERENCES categories(name) ON DELETE CASCADE,
+    phrase TEXT NOT NULL,
+    language TEXT,
+    PRIMARY KEY (category_name, phrase)
+);
@@ -0,0 +1,2 @@
+ALTER TABLE categories
+    ADD COLUMN expression TEXT;
@@ -0,0 +1,8 @@
+CREATE TABLE sync_state (
+    country_iso_alpha_3 VARCHAR(3) REFERENCES countries(iso_alpha_3),
+    category_name TEXT REFERENCES categories(name) ON DELETE CASCADE,
+    -- Articles seen up to this point have been fetched without failures
+    synced_until timestamptz NOT NULL,
+    updated_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (country_iso_alpha_3, category_name)
+);
@@ -0,0 +1,27 @@
+CREATE TABLE sync_runs (
+    id SERIAL PRIMARY KEY,
+    mode TEXT NOT NULL,
+    started_at timestamptz NOT NULL,
+    finished_at timestamptz NOT NULL,
+    fetched INT NOT NULL,
+    inserted INT NOT NULL,
+    duplicates INT NOT NULL,
+    failed_windows INT NOT NULL,
+    failed_queries INT NOT NULL
+);
+
+-- Countries and categories aren't foreign keys so the reports outlive them
+CREATE TABLE sync_run_queries (
+    sync_run_id INT NOT NULL REFERENCES sync_runs(id) ON DELETE CASCADE,
+    country_iso_alpha_3 VARCHAR(3) NOT NULL,
+    category_name TEXT NOT NULL,
+    inclusive_start_date timestamptz NOT NULL,
+    inclusive_end_date timestamptz NOT NULL,
+    fetched INT NOT NULL,
+    inserted INT NOT NULL,
+    duplicates INT NOT NULL,
+    failed_windows INT NOT NULL,
+    errors TEXT[] NOT NULL,
+    duration_ms BIGINT NOT NULL,
+    PRIMARY KEY (sync_run_id, country_iso_alpha_3, category_name)
+);
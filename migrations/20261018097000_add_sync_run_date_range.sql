@@ -0,0 +1,3 @@
+ALTER TABLE sync_runs
+    ADD COLUMN inclusive_start_date timestamptz,
+    ADD COLUMN inclusive_end_date timestamptz;
@@ -0,0 +1,23 @@
+-- Queries waiting for, or being worked on by, the sync workers
+CREATE TABLE sync_jobs (
+    id BIGSERIAL PRIMARY KEY,
+    country_iso_alpha_3 VARCHAR(3) NOT NULL,
+    category_name TEXT NOT NULL REFERENCES categories(name) ON DELETE CASCADE,
+    inclusive_start_date timestamptz NOT NULL,
+    inclusive_end_date timestamptz NOT NULL,
+    -- queued, running, completed or failed
+    status TEXT NOT NULL DEFAULT 'queued',
+    attempts INT NOT NULL DEFAULT 0,
+    max_attempts INT NOT NULL DEFAULT 3,
+    worker_id TEXT,
+    -- A running job whose lease expired belongs to a worker that stopped, it can be claimed again
+    lease_expires_at timestamptz,
+    heartbeat_at timestamptz,
+    inserted INT,
+    last_error TEXT,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    finished_at timestamptz
+);
+
+CREATE INDEX sync_jobs_claimable_idx ON sync_jobs (status, id)
+    WHERE status IN ('queued', 'running');
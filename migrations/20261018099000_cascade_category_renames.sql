@@ -0,0 +1,31 @@
+-- Renaming a category renames it everywhere it is referenced, deleting it removes what only
+-- makes sense with the category. Articles are untagged rather than deleted, see delete_category.
+ALTER TABLE news_article_categories
+    DROP CONSTRAINT news_article_categories_category_name_fkey,
+    ADD CONSTRAINT news_article_categories_category_name_fkey
+        FOREIGN KEY (category_name) REFERENCES categories(name) ON UPDATE CASCADE ON DELETE CASCADE;
+
+ALTER TABLE coverage_timeline
+    DROP CONSTRAINT coverage_timeline_category_name_fkey,
+    ADD CONSTRAINT coverage_timeline_category_name_fkey
+        FOREIGN KEY (category_name) REFERENCES categories(name) ON UPDATE CASCADE ON DELETE CASCADE;
+
+ALTER TABLE tone_timeline
+    DROP CONSTRAINT tone_timeline_category_name_fkey,
+    ADD CONSTRAINT tone_timeline_category_name_fkey
+        FOREIGN KEY (category_name) REFERENCES categories(name) ON UPDATE CASCADE ON DELETE CASCADE;
+
+ALTER TABLE category_keywords
+    DROP CONSTRAINT category_keywords_category_name_fkey,
+    ADD CONSTRAINT category_keywords_category_name_fkey
+        FOREIGN KEY (category_name) REFERENCES categories(name) ON UPDATE CASCADE ON DELETE CASCADE;
+
+ALTER TABLE sync_state
+    DROP CONSTRAINT sync_state_category_name_fkey,
+    ADD CONSTRAINT sync_state_category_name_fkey
+        FOREIGN KEY (category_name) REFERENCES categories(name) ON UPDATE CASCADE ON DELETE CASCADE;
+
+ALTER TABLE sync_jobs
+    DROP CONSTRAINT sync_jobs_category_name_fkey,
+    ADD CONSTRAINT sync_jobs_category_name_fkey
+        FOREIGN KEY (category_name) REFERENCES categories(name) ON UPDATE CASCADE ON DELETE CASCADE;
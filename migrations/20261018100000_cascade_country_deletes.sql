@@ -0,0 +1,16 @@
+-- Removing a country removes its sync state and timelines, its articles have to be purged
+-- explicitly, see remove_country
+ALTER TABLE coverage_timeline
+    DROP CONSTRAINT coverage_timeline_country_iso_alpha_3_fkey,
+    ADD CONSTRAINT coverage_timeline_country_iso_alpha_3_fkey
+        FOREIGN KEY (country_iso_alpha_3) REFERENCES countries(iso_alpha_3) ON DELETE CASCADE;
+
+ALTER TABLE tone_timeline
+    DROP CONSTRAINT tone_timeline_country_iso_alpha_3_fkey,
+    ADD CONSTRAINT tone_timeline_country_iso_alpha_3_fkey
+        FOREIGN KEY (country_iso_alpha_3) REFERENCES countries(iso_alpha_3) ON DELETE CASCADE;
+
+ALTER TABLE sync_state
+    DROP CONSTRAINT sync_state_country_iso_alpha_3_fkey,
+    ADD CONSTRAINT sync_state_country_iso_alpha_3_fkey
+        FOREIGN KEY (country_iso_alpha_3) REFERENCES countries(iso_alpha_3) ON DELETE CASCADE;
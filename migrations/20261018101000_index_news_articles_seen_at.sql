@@ -0,0 +1,2 @@
+-- Pages of articles are read in (seen_at, id) order starting after a cursor
+CREATE INDEX news_articles_seen_at_id_idx ON news_articles (seen_at, id);
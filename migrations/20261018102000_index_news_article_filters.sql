@@ -0,0 +1,9 @@
+-- Indexes behind the article filters. Languages and domains are compared lowercased, titles
+-- are searched with ILIKE '%...%' which only a trigram index can serve.
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX news_articles_country_seen_at_idx ON news_articles (country_iso_alpha_3, seen_at);
+CREATE INDEX news_articles_language_idx ON news_articles (lower(language));
+CREATE INDEX news_articles_domain_idx ON news_articles (lower(domain));
+CREATE INDEX news_articles_title_trgm_idx ON news_articles USING gin (title gin_trgm_ops);
+CREATE INDEX news_article_categories_category_name_idx ON news_article_categories (category_name);
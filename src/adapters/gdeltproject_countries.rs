@@ -0,0 +1,389 @@
+use isocountry::CountryCode;
+
+// GDELT identifies countries with FIPS 10-4 codes, both in the `sourcecountry:` query operator
+// and in the country names it returns. Each row holds the FIPS code, the name GDELT reports for
+// the source country and the matching iso country. West Bank and Gaza Strip are separate FIPS
+// entities that both map to Palestine.
+const COUNTRIES: [(&str, &str, CountryCode); 247] = [
+    ("AF", "Afghanistan", CountryCode::AFG),
+    ("AL", "Albania", CountryCode::ALB),
+    ("AG", "Algeria", CountryCode::DZA),
+    ("AQ", "American Samoa", CountryCode::ASM),
+    ("AN", "Andorra", CountryCode::AND),
+    ("AO", "Angola", CountryCode::AGO),
+    ("AV", "Anguilla", CountryCode::AIA),
+    ("AY", "Antarctica", CountryCode::ATA),
+    ("AC", "Antigua and Barbuda", CountryCode::ATG),
+    ("AR", "Argentina", CountryCode::ARG),
+    ("AM", "Armenia", CountryCode::ARM),
+    ("AA", "Aruba", CountryCode::ABW),
+    ("AS", "Australia", CountryCode::AUS),
+    ("AU", "Austria", CountryCode::AUT),
+    ("AJ", "Azerbaijan", CountryCode::AZE),
+    ("BF", "Bahamas", CountryCode::BHS),
+    ("BA", "Bahrain", CountryCode::BHR),
+    ("BG", "Bangladesh", CountryCode::BGD),
+    ("BB", "Barbados", CountryCode::BRB),
+    ("BO", "Belarus", CountryCode::BLR),
+    ("BE", "Belgium", CountryCode::BEL),
+    ("BH", "Belize", CountryCode::BLZ),
+    ("BN", "Benin", CountryCode::BEN),
+    ("BD", "Bermuda", CountryCode::BMU),
+    ("BT", "Bhutan", CountryCode::BTN),
+    ("BL", "Bolivia", CountryCode::BOL),
+    ("BK", "Bosnia and Herzegovina", CountryCode::BIH),
+    ("BC", "Botswana", CountryCode::BWA),
+    ("BV", "Bouvet Island", CountryCode::BVT),
+    ("BR", "Brazil", CountryCode::BRA),
+    ("IO", "British Indian Ocean Territory", CountryCode::IOT),
+    ("VI", "British Virgin Islands", CountryCode::VGB),
+    ("BX", "Brunei", CountryCode::BRN),
+    ("BU", "Bulgaria", CountryCode::BGR),
+    ("UV", "Burkina Faso", CountryCode::BFA),
+    ("BM", "Myanmar", CountryCode::MMR),
+    ("BY", "Burundi", CountryCode::BDI),
+    ("CB", "Cambodia", CountryCode::KHM),
+    ("CM", "Cameroon", CountryCode::CMR),
+    ("CA", "Canada", CountryCode::CAN),
+    ("CV", "Cape Verde", CountryCode::CPV),
+    ("CJ", "Cayman Islands", CountryCode::CYM),
+    ("CT", "Central African Republic", CountryCode::CAF),
+    ("CD", "Chad", CountryCode::TCD),
+    ("CI", "Chile", CountryCode::CHL),
+    ("CH", "China", CountryCode::CHN),
+    ("KT", "Christmas Island", CountryCode::CXR),
+    ("CK", "Cocos Islands", CountryCode::CCK),
+    ("CO", "Colombia", CountryCode::COL),
+    ("CN", "Comoros", CountryCode::COM),
+    ("CG", "Democratic Republic of the Congo", CountryCode::COD),
+    ("CF", "Republic of the Congo", CountryCode::COG),
+    ("CW", "Cook Islands", CountryCode::COK),
+    ("CS", "Costa Rica", CountryCode::CRI),
+    ("IV", "Ivory Coast", CountryCode::CIV),
+    ("HR", "Croatia", CountryCode::HRV),
+    ("CU", "Cuba", CountryCode::CUB),
+    ("UC", "Curacao", CountryCode::CUW),
+    ("CY", "Cyprus", CountryCode::CYP),
+    ("EZ", "Czech Republic", CountryCode::CZE),
+    ("DA", "Denmark", CountryCode::DNK),
+    ("DJ", "Djibouti", CountryCode::DJI),
+    ("DO", "Dominica", CountryCode::DMA),
+    ("DR", "Dominican Republic", CountryCode::DOM),
+    ("EC", "Ecuador", CountryCode::ECU),
+    ("EG", "Egypt", CountryCode::EGY),
+    ("ES", "El Salvador", CountryCode::SLV),
+    ("EK", "Equatorial Guinea", CountryCode::GNQ),
+    ("ER", "Eritrea", CountryCode::ERI),
+    ("EN", "Estonia", CountryCode::EST),
+    ("WZ", "Swaziland", CountryCode::SWZ),
+    ("ET", "Ethiopia", CountryCode::ETH),
+    ("FK", "Falkland Islands", CountryCode::FLK),
+    ("FO", "Faroe Islands", CountryCode::FRO),
+    ("FJ", "Fiji", CountryCode::FJI),
+    ("FI", "Finland", CountryCode::FIN),
+    ("FR", "France", CountryCode::FRA),
+    ("FG", "French Guiana", CountryCode::GUF),
+    ("FP", "French Polynesia", CountryCode::PYF),
+    (
+        "FS",
+        "French Southern and Antarctic Lands",
+        CountryCode::ATF,
+    ),
+    ("GB", "Gabon", CountryCode::GAB),
+    ("GA", "Gambia", CountryCode::GMB),
+    ("GG", "Georgia", CountryCode::GEO),
+    ("GM", "Germany", CountryCode::DEU),
+    ("GH", "Ghana", CountryCode::GHA),
+    ("GI", "Gibraltar", CountryCode::GIB),
+    ("GR", "Greece", CountryCode::GRC),
+    ("GL", "Greenland", CountryCode::GRL),
+    ("GJ", "Grenada", CountryCode::GRD),
+    ("GP", "Guadeloupe", CountryCode::GLP),
+    ("GQ", "Guam", CountryCode::GUM),
+    ("GT", "Guatemala", CountryCode::GTM),
+    ("GK", "Guernsey", CountryCode::GGY),
+    ("GV", "Guinea", CountryCode::GIN),
+    ("PU", "Guinea-Bissau", CountryCode::GNB),
+    ("GY", "Guyana", CountryCode::GUY),
+    ("HA", "Haiti", CountryCode::HTI),
+    ("HM", "Heard Island and McDonald Islands", CountryCode::HMD),
+    ("VT", "Vatican City", CountryCode::VAT),
+    ("HO", "Honduras", CountryCode::HND),
+    ("HK", "Hong Kong", CountryCode::HKG),
+    ("HU", "Hungary", CountryCode::HUN),
+    ("IC", "Iceland", CountryCode::ISL),
+    ("IN", "India", CountryCode::IND),
+    ("ID", "Indonesia", CountryCode::IDN),
+    ("IR", "Iran", CountryCode::IRN),
+    ("IZ", "Iraq", CountryCode::IRQ),
+    ("EI", "Ireland", CountryCode::IRL),
+    ("IM", "Isle of Man", CountryCode::IMN),
+    ("IS", "Israel", CountryCode::ISR),
+    ("IT", "Italy", CountryCode::ITA),
+    ("JM", "Jamaica", CountryCode::JAM),
+    ("JA", "Japan", CountryCode::JPN),
+    ("JE", "Jersey", CountryCode::JEY),
+    ("JO", "Jordan", CountryCode::JOR),
+    ("KZ", "Kazakhstan", CountryCode::KAZ),
+    ("KE", "Kenya", CountryCode::KEN),
+    ("KR", "Kiribati", CountryCode::KIR),
+    ("KN", "North Korea", CountryCode::PRK),
+    ("KS", "South Korea", CountryCode::KOR),
+    ("KU", "Kuwait", CountryCode::KWT),
+    ("KG", "Kyrgyzstan", CountryCode::KGZ),
+    ("LA", "Laos", CountryCode::LAO),
+    ("LG", "Latvia", CountryCode::LVA),
+    ("LE", "Lebanon", CountryCode::LBN),
+    ("LT", "Lesotho", CountryCode::LSO),
+    ("LI", "Liberia", CountryCode::LBR),
+    ("LY", "Libya", CountryCode::LBY),
+    ("LS", "Liechtenstein", CountryCode::LIE),
+    ("LH", "Lithuania", CountryCode::LTU),
+    ("LU", "Luxembourg", CountryCode::LUX),
+    ("MC", "Macau", CountryCode::MAC),
+    ("MK", "Macedonia", CountryCode::MKD),
+    ("MA", "Madagascar", CountryCode::MDG),
+    ("MI", "Malawi", CountryCode::MWI),
+    ("MY", "Malaysia", CountryCode::MYS),
+    ("MV", "Maldives", CountryCode::MDV),
+    ("ML", "Mali", CountryCode::MLI),
+    ("MT", "Malta", CountryCode::MLT),
+    ("RM", "Marshall Islands", CountryCode::MHL),
+    ("MB", "Martinique", CountryCode::MTQ),
+    ("MR", "Mauritania", CountryCode::MRT),
+    ("MP", "Mauritius", CountryCode::MUS),
+    ("MF", "Mayotte", CountryCode::MYT),
+    ("MX", "Mexico", CountryCode::MEX),
+    ("FM", "Micronesia", CountryCode::FSM),
+    ("MD", "Moldova", CountryCode::MDA),
+    ("MN", "Monaco", CountryCode::MCO),
+    ("MG", "Mongolia", CountryCode::MNG),
+    ("MJ", "Montenegro", CountryCode::MNE),
+    ("MH", "Montserrat", CountryCode::MSR),
+    ("MO", "Morocco", CountryCode::MAR),
+    ("MZ", "Mozambique", CountryCode::MOZ),
+    ("WA", "Namibia", CountryCode::NAM),
+    ("NR", "Nauru", CountryCode::NRU),
+    ("NP", "Nepal", CountryCode::NPL),
+    ("NL", "Netherlands", CountryCode::NLD),
+    ("NC", "New Caledonia", CountryCode::NCL),
+    ("NZ", "New Zealand", CountryCode::NZL),
+    ("NU", "Nicaragua", CountryCode::NIC),
+    ("NG", "Niger", CountryCode::NER),
+    ("NI", "Nigeria", CountryCode::NGA),
+    ("NE", "Niue", CountryCode::NIU),
+    ("NF", "Norfolk Island", CountryCode::NFK),
+    ("CQ", "Northern Mariana Islands", CountryCode::MNP),
+    ("NO", "Norway", CountryCode::NOR),
+    ("MU", "Oman", CountryCode::OMN),
+    ("PK", "Pakistan", CountryCode::PAK),
+    ("PS", "Palau", CountryCode::PLW),
+    ("WE", "West Bank", CountryCode::PSE),
+    ("GZ", "Gaza Strip", CountryCode::PSE),
+    ("PM", "Panama", CountryCode::PAN),
+    ("PP", "Papua New Guinea", CountryCode::PNG),
+    ("PA", "Paraguay", CountryCode::PRY),
+    ("PE", "Peru", CountryCode::PER),
+    ("RP", "Philippines", CountryCode::PHL),
+    ("PC", "Pitcairn Islands", CountryCode::PCN),
+    ("PL", "Poland", CountryCode::POL),
+    ("PO", "Portugal", CountryCode::PRT),
+    ("RQ", "Puerto Rico", CountryCode::PRI),
+    ("QA", "Qatar", CountryCode::QAT),
+    ("RE", "Reunion", CountryCode::REU),
+    ("RO", "Romania", CountryCode::ROU),
+    ("RS", "Russia", CountryCode::RUS),
+    ("RW", "Rwanda", CountryCode::RWA),
+    ("TB", "Saint Barthelemy", CountryCode::BLM),
+    ("SH", "Saint Helena", CountryCode::SHN),
+    ("SC", "Saint Kitts and Nevis", CountryCode::KNA),
+    ("ST", "Saint Lucia", CountryCode::LCA),
+    ("RN", "Saint Martin", CountryCode::MAF),
+    ("SB", "Saint Pierre and Miquelon", CountryCode::SPM),
+    ("VC", "Saint Vincent and the Grenadines", CountryCode::VCT),
+    ("WS", "Samoa", CountryCode::WSM),
+    ("SM", "San Marino", CountryCode::SMR),
+    ("TP", "Sao Tome and Principe", CountryCode::STP),
+    ("SA", "Saudi Arabia", CountryCode::SAU),
+    ("SG", "Senegal", CountryCode::SEN),
+    ("RI", "Serbia", CountryCode::SRB),
+    ("SE", "Seychelles", CountryCode::SYC),
+    ("SL", "Sierra Leone", CountryCode::SLE),
+    ("SN", "Singapore", CountryCode::SGP),
+    ("NN", "Sint Maarten", CountryCode::SXM),
+    ("LO", "Slovakia", CountryCode::SVK),
+    ("SI", "Slovenia", CountryCode::SVN),
+    ("BP", "Solomon Islands", CountryCode::SLB),
+    ("SO", "Somalia", CountryCode::SOM),
+    ("SF", "South Africa", CountryCode::ZAF),
+    (
+        "SX",
+        "South Georgia and the South Sandwich Islands",
+        CountryCode::SGS,
+    ),
+    ("OD", "South Sudan", CountryCode::SSD),
+    ("SP", "Spain", CountryCode::ESP),
+    ("CE", "Sri Lanka", CountryCode::LKA),
+    ("SU", "Sudan", CountryCode::SDN),
+    ("NS", "Suriname", CountryCode::SUR),
+    ("SV", "Svalbard", CountryCode::SJM),
+    ("SW", "Sweden", CountryCode::SWE),
+    ("SZ", "Switzerland", CountryCode::CHE),
+    ("SY", "Syria", CountryCode::SYR),
+    ("TW", "Taiwan", CountryCode::TWN),
+    ("TI", "Tajikistan", CountryCode::TJK),
+    ("TZ", "Tanzania", CountryCode::TZA),
+    ("TH", "Thailand", CountryCode::THA),
+    ("TT", "East Timor", CountryCode::TLS),
+    ("TO", "Togo", CountryCode::TGO),
+    ("TL", "Tokelau", CountryCode::TKL),
+    ("TN", "Tonga", CountryCode::TON),
+    ("TD", "Trinidad and Tobago", CountryCode::TTO),
+    ("TS", "Tunisia", CountryCode::TUN),
+    ("TU", "Turkey", CountryCode::TUR),
+    ("TX", "Turkmenistan", CountryCode::TKM),
+    ("TK", "Turks and Caicos Islands", CountryCode::TCA),
+    ("TV", "Tuvalu", CountryCode::TUV),
+    ("UG", "Uganda", CountryCode::UGA),
+    ("UP", "Ukraine", CountryCode::UKR),
+    ("AE", "United Arab Emirates", CountryCode::ARE),
+    ("UK", "United Kingdom", CountryCode::GBR),
+    ("US", "United States", CountryCode::USA),
+    ("UY", "Uruguay", CountryCode::URY),
+    ("UZ", "Uzbekistan", CountryCode::UZB),
+    ("NH", "Vanuatu", CountryCode::VUT),
+    ("VE", "Venezuela", CountryCode::VEN),
+    ("VM", "Vietnam", CountryCode::VNM),
+    ("VQ", "Virgin Islands", CountryCode::VIR),
+    ("WF", "Wallis and Futuna", CountryCode::WLF),
+    ("WI", "Western Sahara", CountryCode::ESH),
+    ("YM", "Yemen", CountryCode::YEM),
+    ("ZA", "Zambia", CountryCode::ZMB),
+    ("ZI", "Zimbabwe", CountryCode::ZWE),
+];
+
+// Other spellings of country names GDELT and common usage have been seen to use
+const NAME_ALIASES: [(&str, CountryCode); 34] = [
+    ("Burma", CountryCode::MMR),
+    ("Bosnia-Herzegovina", CountryCode::BIH),
+    ("Bosnia", CountryCode::BIH),
+    ("Cote d'Ivoire", CountryCode::CIV),
+    ("Côte d'Ivoire", CountryCode::CIV),
+    ("Congo", CountryCode::COG),
+    ("Czechia", CountryCode::CZE),
+    ("Eswatini", CountryCode::SWZ),
+    ("Holy See", CountryCode::VAT),
+    ("North Macedonia", CountryCode::MKD),
+    ("Timor-Leste", CountryCode::TLS),
+    ("Palestine", CountryCode::PSE),
+    ("Occupied Palestinian Territory", CountryCode::PSE),
+    ("Great Britain", CountryCode::GBR),
+    ("UK", CountryCode::GBR),
+    ("USA", CountryCode::USA),
+    ("United States of America", CountryCode::USA),
+    ("Russian Federation", CountryCode::RUS),
+    ("Korea, South", CountryCode::KOR),
+    ("Republic of Korea", CountryCode::KOR),
+    ("Korea, North", CountryCode::PRK),
+    ("Viet Nam", CountryCode::VNM),
+    ("Macao", CountryCode::MAC),
+    ("Cabo Verde", CountryCode::CPV),
+    ("Turkiye", CountryCode::TUR),
+    ("Türkiye", CountryCode::TUR),
+    ("Brunei Darussalam", CountryCode::BRN),
+    ("Lao People's Democratic Republic", CountryCode::LAO),
+    ("Congo, Democratic Republic of the", CountryCode::COD),
+    ("Congo, Republic of the", CountryCode::COG),
+    ("The Bahamas", CountryCode::BHS),
+    ("The Gambia", CountryCode::GMB),
+    ("Vatican", CountryCode::VAT),
+    ("Netherlands Antilles", CountryCode::CUW),
+];
+
+// All FIPS codes covering the country, more than one for countries GDELT splits up
+pub fn to_fips_codes(country: CountryCode) -> Vec<&'static str> {
+    COUNTRIES
+        .iter()
+        .filter(|(_, _, iso)| *iso == country)
+        .map(|(fips, _, _)| *fips)
+        .collect()
+}
+
+pub fn from_fips_code(code: &str) -> Option<CountryCode> {
+    COUNTRIES
+        .iter()
+        .find(|(fips, _, _)| fips.eq_ignore_ascii_case(code.trim()))
+        .map(|(_, _, iso)| *iso)
+}
+
+// Maps the `sourcecountry` value of an article to its iso country, accepting GDELT names, known
+// aliases, iso names and FIPS codes
+pub fn from_gdelt_name(name: &str) -> Option<CountryCode> {
+    let name = name.trim();
+    COUNTRIES
+        .iter()
+        .find(|(_, gdelt_name, _)| gdelt_name.eq_ignore_ascii_case(name))
+        .map(|(_, _, iso)| *iso)
+        .or_else(|| {
+            NAME_ALIASES
+                .iter()
+                .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+                .map(|(_, iso)| *iso)
+        })
+        .or_else(|| {
+            CountryCode::iter()
+                .find(|iso| iso.name().eq_ignore_ascii_case(name))
+                .copied()
+        })
+        .or_else(|| {
+            if name.len() == 2 {
+                from_fips_code(name)
+            } else {
+                None
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_to_fips_codes() {
+        assert_eq!(to_fips_codes(CountryCode::FRA), vec!["FR"]);
+        assert_eq!(to_fips_codes(CountryCode::DEU), vec!["GM"]);
+        assert_eq!(to_fips_codes(CountryCode::GBR), vec!["UK"]);
+        assert_eq!(to_fips_codes(CountryCode::PSE), vec!["WE", "GZ"]);
+    }
+
+    #[test]
+    fn test_from_fips_code() {
+        assert_eq!(from_fips_code("GM"), Some(CountryCode::DEU));
+        assert_eq!(from_fips_code("sp"), Some(CountryCode::ESP));
+        assert_eq!(from_fips_code("XX"), None);
+    }
+
+    #[test]
+    fn test_from_gdelt_name() {
+        assert_eq!(from_gdelt_name("France"), Some(CountryCode::FRA));
+        assert_eq!(from_gdelt_name("United States"), Some(CountryCode::USA));
+        assert_eq!(from_gdelt_name("United Kingdom"), Some(CountryCode::GBR));
+        assert_eq!(from_gdelt_name("russia"), Some(CountryCode::RUS));
+        assert_eq!(
+            from_gdelt_name("Bosnia-Herzegovina"),
+            Some(CountryCode::BIH)
+        );
+        assert_eq!(from_gdelt_name("Fake"), None);
+    }
+
+    #[test]
+    fn test_country_table_is_consistent() {
+        let mut codes = HashSet::new();
+        for (fips, name, iso) in COUNTRIES.iter() {
+            assert!(codes.insert(*fips), "duplicate FIPS code {}", fips);
+            assert_eq!(from_fips_code(fips), Some(*iso));
+            assert_eq!(from_gdelt_name(name), Some(*iso));
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use rand::Rng;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::{sleep_until, Instant};
+
+#[derive(Debug, Clone)]
+pub struct RequestSchedulerConfig {
+    // Minimum time between two requests, shared by every query made through the adapter
+    pub min_interval: Duration,
+    // Number of times a window is requested before it is reported as failed
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RequestSchedulerConfig {
+    fn default() -> Self {
+        Self {
+            // The api answers with "Please limit requests to one every 5 seconds" when called faster
+            min_interval: Duration::from_secs(5),
+            max_attempts: 5,
+            initial_backoff: Duration::from_secs(10),
+            max_backoff: Duration::from_secs(120),
+        }
+    }
+}
+
+// Decides when the next request to the GDELT api may be made
+pub struct RequestScheduler {
+    config: RequestSchedulerConfig,
+    next_request_at: Mutex<Instant>,
+}
+
+impl RequestScheduler {
+    pub fn new(config: RequestSchedulerConfig) -> Self {
+        Self {
+            config,
+            next_request_at: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.config.max_attempts.max(1)
+    }
+
+    // Waits until the rate limit allows another request. The lock is held while sleeping so
+    // concurrent callers are let through one at a time, in the order they arrived.
+    pub async fn wait_for_turn(&self) {
+        let mut next_request_at = self.next_request_at.lock().await;
+        sleep_until(*next_request_at).await;
+        *next_request_at = Instant::now() + self.config.min_interval;
+    }
+
+    // Exponential backoff with jitter for the given (1 based) attempt, the delay is picked
+    // between half and the whole of the exponential delay so retrying callers spread out.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(16);
+        let delay = self
+            .config
+            .initial_backoff
+            .saturating_mul(2u32.pow(exponent))
+            .min(self.config.max_backoff);
+        let half = delay / 2;
+        half + half.mul_f64(rand::thread_rng().gen::<f64>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let scheduler = RequestScheduler::new(RequestSchedulerConfig {
+            min_interval: Duration::from_secs(5),
+            max_attempts: 3,
+            initial_backoff: Duration::from_secs(10),
+            max_backoff: Duration::from_secs(60),
+        });
+        let first = scheduler.backoff(1);
+        assert!(first >= Duration::from_secs(5) && first <= Duration::from_secs(10));
+        let second = scheduler.backoff(2);
+        assert!(second >= Duration::from_secs(10) && second <= Duration::from_secs(20));
+        let capped = scheduler.backoff(10);
+        assert!(capped >= Duration::from_secs(30) && capped <= Duration::from_secs(60));
+    }
+}
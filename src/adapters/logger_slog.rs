@@ -21,6 +21,12 @@ impl SlogLoggerAdapter {
     }
 }
 
+impl Default for SlogLoggerAdapter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ports::Logger for SlogLoggerAdapter {
     fn debug(&self, msg: &str) {
         let logger = self.logger.lock().unwrap();
@@ -1,3 +1,9 @@
+pub mod gdeltproject_countries;
+pub mod gdeltproject_scheduler;
 pub mod logger_slog;
+pub mod news_archive_gdelt_files;
 pub mod news_repository_postgres;
 pub mod news_search_client_gdeltproject;
+pub mod postgres_error;
+pub mod sync_job_queue_postgres;
+pub mod sync_lock_postgres;
@@ -0,0 +1,583 @@
+use crate::core::domain::{CategoryExpression, NewsArticle};
+use crate::core::ports;
+use crate::core::ports::{ArchiveError, NewsArchive};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use csv::ByteRecord;
+use isocountry::CountryCode;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+
+// Number of articles sent to the channel at once
+const BATCH_SIZE: usize = 500;
+
+// Reads the GDELT 2.0 "mentions" and "GKG" export files found in a local directory, either as
+// downloaded (zipped) or unzipped. The exports don't carry the source country of an article so it
+// is derived from the country code top level domain of the source, articles from generic domains
+// such as .com are skipped.
+pub struct GDeltaFilesNewsArchive {
+    logger: Box<dyn ports::Logger>,
+    directory: PathBuf,
+}
+
+impl GDeltaFilesNewsArchive {
+    pub fn new(logger: Box<dyn ports::Logger>, directory: PathBuf) -> Self {
+        Self { logger, directory }
+    }
+}
+
+#[async_trait]
+impl NewsArchive for GDeltaFilesNewsArchive {
+    async fn read_articles(
+        &self,
+        countries: Vec<CountryCode>,
+        categories: Vec<(String, CategoryExpression)>,
+        channel: mpsc::Sender<Vec<NewsArticle>>,
+    ) -> Result<i32, ArchiveError> {
+        let files = list_export_files(&self.directory)?;
+        self.logger.info(
+            format!(
+                "Found {} export files in {}",
+                files.len(),
+                self.directory.display()
+            )
+            .as_str(),
+        );
+        let logger = self.logger.clone_box();
+        let filter = ExportFilter {
+            countries,
+            categories,
+        };
+
+        // Reading and unzipping is blocking work, keep it off the async worker threads
+        tokio::task::spawn_blocking(move || {
+            let mut files_read = 0;
+            for (path, kind) in files {
+                match read_export_file(&path, kind, &filter, &channel) {
+                    Ok(num_articles) => {
+                        files_read += 1;
+                        logger.debug(
+                            format!(
+                                "Read {} matching articles from {}",
+                                num_articles,
+                                path.display()
+                            )
+                            .as_str(),
+                        );
+                    }
+                    // Nobody is receiving the articles any more, the other files would fail too
+                    Err(ArchiveError::Closed) => return Err(ArchiveError::Closed),
+                    Err(e) => {
+                        logger.error(format!("Error reading {}: {}", path.display(), e).as_str())
+                    }
+                }
+            }
+            Ok(files_read)
+        })
+        .await
+        .map_err(|e| ArchiveError::Other(e.to_string()))?
+    }
+}
+
+impl From<zip::result::ZipError> for ArchiveError {
+    fn from(err: zip::result::ZipError) -> Self {
+        ArchiveError::Corrupt(err.to_string())
+    }
+}
+
+impl From<csv::Error> for ArchiveError {
+    fn from(err: csv::Error) -> Self {
+        ArchiveError::Corrupt(err.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ExportKind {
+    Mentions,
+    Gkg,
+}
+
+struct ExportFilter {
+    countries: Vec<CountryCode>,
+    categories: Vec<(String, CategoryExpression)>,
+}
+
+// The parts of a mentions or GKG record we need to build articles
+#[derive(Debug)]
+struct ExportRecord {
+    url: String,
+    domain: String,
+    seen_at: DateTime<Utc>,
+    title: String,
+    themes: Vec<String>,
+    language: String,
+}
+
+fn list_export_files(directory: &Path) -> Result<Vec<(PathBuf, ExportKind)>, ArchiveError> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        if let Some(kind) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(export_kind)
+        {
+            files.push((path, kind));
+        }
+    }
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(files)
+}
+
+// e.g. 20230617164500.gkg.csv.zip or 20230617164500.translation.mentions.CSV
+fn export_kind(file_name: &str) -> Option<ExportKind> {
+    let file_name = file_name.to_lowercase();
+    if file_name.contains(".gkg.") {
+        Some(ExportKind::Gkg)
+    } else if file_name.contains(".mentions.") {
+        Some(ExportKind::Mentions)
+    } else {
+        None
+    }
+}
+
+fn read_export_file(
+    path: &Path,
+    kind: ExportKind,
+    filter: &ExportFilter,
+    channel: &mpsc::Sender<Vec<NewsArticle>>,
+) -> Result<usize, ArchiveError> {
+    let file = File::open(path)?;
+    let is_zipped = path
+        .extension()
+        .map(|extension| extension.eq_ignore_ascii_case("zip"))
+        .unwrap_or(false);
+    if !is_zipped {
+        return read_records(file, kind, filter, channel);
+    }
+
+    let mut archive = zip::ZipArchive::new(file)?;
+    let mut num_articles = 0;
+    for i in 0..archive.len() {
+        num_articles += read_records(archive.by_index(i)?, kind, filter, channel)?;
+    }
+    Ok(num_articles)
+}
+
+fn read_records<R: Read>(
+    reader: R,
+    kind: ExportKind,
+    filter: &ExportFilter,
+    channel: &mpsc::Sender<Vec<NewsArticle>>,
+) -> Result<usize, ArchiveError> {
+    // Fields are tab separated and may contain unescaped quotes
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .delimiter(b'\t')
+        .has_headers(false)
+        .flexible(true)
+        .quoting(false)
+        .from_reader(reader);
+    let mut record = ByteRecord::new();
+    let mut batch = Vec::new();
+    let mut num_articles = 0;
+
+    while csv_reader.read_byte_record(&mut record)? {
+        let parsed = match kind {
+            ExportKind::Gkg => parse_gkg_record(&record),
+            ExportKind::Mentions => parse_mentions_record(&record),
+        };
+        if let Some(parsed) = parsed {
+            batch.extend(to_news_article(&parsed, filter));
+        }
+        if batch.len() >= BATCH_SIZE {
+            num_articles += batch.len();
+            channel
+                .blocking_send(std::mem::take(&mut batch))
+                .map_err(|_| ArchiveError::Closed)?;
+        }
+    }
+    if !batch.is_empty() {
+        num_articles += batch.len();
+        channel
+            .blocking_send(batch)
+            .map_err(|_| ArchiveError::Closed)?;
+    }
+    Ok(num_articles)
+}
+
+fn field(record: &ByteRecord, index: usize) -> String {
+    record
+        .get(index)
+        .map(|value| String::from_utf8_lossy(value).trim().to_string())
+        .unwrap_or_default()
+}
+
+fn parse_gkg_record(record: &ByteRecord) -> Option<ExportRecord> {
+    let url = field(record, 4);
+    if !url.starts_with("http") {
+        return None;
+    }
+    let themes = field(record, 7)
+        .split(';')
+        .filter(|theme| !theme.is_empty())
+        .map(String::from)
+        .collect();
+    let title = page_title(&field(record, 26)).unwrap_or_else(|| url.clone());
+    Some(ExportRecord {
+        domain: field(record, 3),
+        seen_at: to_datetime(&field(record, 1))?,
+        title,
+        themes,
+        language: to_language(&field(record, 25)),
+        url,
+    })
+}
+
+fn parse_mentions_record(record: &ByteRecord) -> Option<ExportRecord> {
+    // Only web mentions identify an article by its url
+    if field(record, 3) != "1" {
+        return None;
+    }
+    let url = field(record, 5);
+    Some(ExportRecord {
+        domain: field(record, 4),
+        seen_at: to_datetime(&field(record, 2))?,
+        // Mentions have no title, the url is the closest thing to one
+        title: url.clone(),
+        themes: Vec::new(),
+        language: to_language(&field(record, 14)),
+        url,
+    })
+}
+
+fn page_title(extras: &str) -> Option<String> {
+    let start = extras.find("<PAGE_TITLE>")? + "<PAGE_TITLE>".len();
+    let end = extras[start..].find("</PAGE_TITLE>")? + start;
+    let title = extras[start..end].trim();
+    if title.is_empty() {
+        None
+    } else {
+        Some(title.to_string())
+    }
+}
+
+fn to_datetime(date: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(date, "%Y%m%d%H%M%S")
+        .ok()
+        .map(|date| DateTime::<Utc>::from_utc(date, Utc))
+}
+
+// Translation info looks like "srclc:fra;eng:GT-FRA 1.0", articles without it are English
+fn to_language(translation_info: &str) -> String {
+    let code = translation_info
+        .split(';')
+        .find_map(|part| part.strip_prefix("srclc:"))
+        .unwrap_or("eng");
+    match code {
+        "eng" => "English",
+        "fra" => "French",
+        "deu" => "German",
+        "spa" => "Spanish",
+        "ita" => "Italian",
+        "por" => "Portuguese",
+        "nld" => "Dutch",
+        "rus" => "Russian",
+        "pol" => "Polish",
+        "tur" => "Turkish",
+        "ara" => "Arabic",
+        "zho" => "Chinese",
+        "jpn" => "Japanese",
+        other => other,
+    }
+    .to_string()
+}
+
+fn country_from_domain(domain: &str) -> Option<CountryCode> {
+    let tld = domain.trim_end_matches('.').rsplit('.').next()?;
+    match tld.to_lowercase().as_str() {
+        "uk" => Some(CountryCode::GBR),
+        tld if tld.len() == 2 => CountryCode::for_alpha2_caseless(tld).ok(),
+        _ => None,
+    }
+}
+
+// A category matches when its search expression matches the words of the title, the url and the
+// GKG themes. Themes glue words together, e.g. ENV_CLIMATECHANGE, so every phrase also matches its
+// words written without spaces.
+fn matching_categories<'a>(
+    record: &ExportRecord,
+    categories: &'a [(String, CategoryExpression)],
+) -> Vec<&'a String> {
+    let text = format!(
+        "{} {} {}",
+        record.title,
+        record.url,
+        record.themes.join(" ")
+    );
+    categories
+        .iter()
+        .filter(|(_, expression)| with_compact_phrases(expression).matches(&text))
+        .map(|(category, _)| category)
+        .collect()
+}
+
+fn with_compact_phrases(expression: &CategoryExpression) -> CategoryExpression {
+    match expression {
+        CategoryExpression::Phrase(phrase) => {
+            let compact: String = phrase.split_whitespace().collect();
+            if compact == *phrase {
+                expression.clone()
+            } else {
+                CategoryExpression::Or(vec![
+                    expression.clone(),
+                    CategoryExpression::Phrase(compact),
+                ])
+            }
+        }
+        CategoryExpression::And(operands) => {
+            CategoryExpression::And(operands.iter().map(with_compact_phrases).collect())
+        }
+        CategoryExpression::Or(operands) => {
+            CategoryExpression::Or(operands.iter().map(with_compact_phrases).collect())
+        }
+        CategoryExpression::Not(operand) => {
+            CategoryExpression::Not(Box::new(with_compact_phrases(operand)))
+        }
+    }
+}
+
+// None when the article isn't from one of the countries or about one of the categories
+fn to_news_article(record: &ExportRecord, filter: &ExportFilter) -> Option<NewsArticle> {
+    let country =
+        country_from_domain(&record.domain).filter(|country| filter.countries.contains(country))?;
+    let categories: Vec<String> = matching_categories(record, &filter.categories)
+        .into_iter()
+        .cloned()
+        .collect();
+    if categories.is_empty() {
+        return None;
+    }
+    Some(NewsArticle::new(
+        record.title.clone(),
+        categories,
+        record.seen_at,
+        record.url.clone(),
+        record.domain.clone(),
+        record.language.clone(),
+        country,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::domain::CategoryKeyword;
+    use chrono::TimeZone;
+    use std::io::Write;
+
+    fn gkg_line(url: &str, domain: &str, themes: &str, title: &str) -> String {
+        let mut fields = vec![String::new(); 27];
+        fields[0] = "20230617164500-0".to_string();
+        fields[1] = "20230617164500".to_string();
+        fields[2] = "1".to_string();
+        fields[3] = domain.to_string();
+        fields[4] = url.to_string();
+        fields[7] = themes.to_string();
+        fields[25] = "srclc:fra;eng:GT-FRA 1.0".to_string();
+        fields[26] = format!("<PAGE_TITLE>{}</PAGE_TITLE>", title);
+        fields.join("\t")
+    }
+
+    fn category(name: &str) -> (String, CategoryExpression) {
+        (
+            name.to_string(),
+            CategoryExpression::for_category(name, None, &[]),
+        )
+    }
+
+    fn filter() -> ExportFilter {
+        ExportFilter {
+            countries: vec![CountryCode::FRA],
+            categories: vec![category("climate change"), category("elections")],
+        }
+    }
+
+    fn record(title: &str, url: &str, themes: &[&str]) -> ExportRecord {
+        ExportRecord {
+            url: url.to_string(),
+            domain: "lemonde.fr".to_string(),
+            seen_at: Utc.with_ymd_and_hms(2023, 6, 17, 16, 45, 0).unwrap(),
+            title: title.to_string(),
+            themes: themes.iter().map(|theme| theme.to_string()).collect(),
+            language: "French".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_matching_categories_on_whole_words() {
+        let categories = vec![category("war")];
+        let award = record(
+            "Software award for a startup",
+            "https://lemonde.fr/software-award",
+            &["SOFTWARE", "TAX_WEAPONS_WARHEADS"],
+        );
+        assert!(matching_categories(&award, &categories).is_empty());
+
+        let war = record("The war goes on", "https://lemonde.fr/a", &[]);
+        assert_eq!(matching_categories(&war, &categories), vec!["war"]);
+    }
+
+    #[test]
+    fn test_matching_categories_with_expression_and_keywords() {
+        let keywords = vec![CategoryKeyword {
+            category: "weather".to_string(),
+            phrase: "heat wave".to_string(),
+            language: None,
+        }];
+        let expression = CategoryExpression::parse("storm AND NOT sport").unwrap();
+        let categories = vec![(
+            "weather".to_string(),
+            CategoryExpression::for_category("weather", Some(&expression), &keywords),
+        )];
+
+        let storm = record("A storm hits Brittany", "https://lemonde.fr/a", &[]);
+        assert_eq!(matching_categories(&storm, &categories), vec!["weather"]);
+        let sport = record("Storm delays the sport final", "https://lemonde.fr/b", &[]);
+        assert!(matching_categories(&sport, &categories).is_empty());
+        let keyword = record("La canicule", "https://lemonde.fr/c", &["HEATWAVE"]);
+        assert_eq!(matching_categories(&keyword, &categories), vec!["weather"]);
+    }
+
+    #[test]
+    fn test_parse_gkg_record() {
+        let line = gkg_line(
+            "https://lemonde.fr/planete/article",
+            "lemonde.fr",
+            "ENV_CLIMATECHANGE;TAX_FNCACT",
+            "La canicule s'installe",
+        );
+        let record = ByteRecord::from(line.split('\t').collect::<Vec<_>>());
+        let parsed = parse_gkg_record(&record).unwrap();
+        assert_eq!(parsed.title, "La canicule s'installe");
+        assert_eq!(parsed.language, "French");
+        assert_eq!(
+            parsed.seen_at,
+            Utc.with_ymd_and_hms(2023, 6, 17, 16, 45, 0).unwrap()
+        );
+
+        let article = to_news_article(&parsed, &filter()).unwrap();
+        assert_eq!(article.categories, vec!["climate change".to_string()]);
+        assert_eq!(article.country, CountryCode::FRA);
+    }
+
+    #[test]
+    fn test_parse_mentions_record() {
+        let line = "1\t20230617\t20230617164500\t1\tlefigaro.fr\thttps://lefigaro.fr/climate-change-summit\t1\t-1\t-1\t-1\t1\t100\t1000\t-2.5\t\t";
+        let record = ByteRecord::from(line.split('\t').collect::<Vec<_>>());
+        let parsed = parse_mentions_record(&record).unwrap();
+        assert_eq!(parsed.language, "English");
+        let article = to_news_article(&parsed, &filter()).unwrap();
+        assert_eq!(article.title, "https://lefigaro.fr/climate-change-summit");
+
+        let not_web = line.replacen("\t1\tlefigaro", "\t2\tlefigaro", 1);
+        let record = ByteRecord::from(not_web.split('\t').collect::<Vec<_>>());
+        assert!(parse_mentions_record(&record).is_none());
+    }
+
+    #[test]
+    fn test_country_from_domain() {
+        assert_eq!(country_from_domain("lemonde.fr"), Some(CountryCode::FRA));
+        assert_eq!(country_from_domain("bbc.co.uk"), Some(CountryCode::GBR));
+        assert_eq!(country_from_domain("nytimes.com"), None);
+    }
+
+    #[tokio::test]
+    async fn test_read_articles() {
+        let directory = std::env::temp_dir().join(format!("gdelt_export_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let plain = gkg_line(
+            "https://lemonde.fr/a",
+            "lemonde.fr",
+            "ENV_CLIMATECHANGE",
+            "A",
+        );
+        std::fs::write(directory.join("20230617164500.gkg.csv"), plain).unwrap();
+
+        let zipped = [
+            gkg_line(
+                "https://lemonde.fr/b",
+                "lemonde.fr",
+                "",
+                "Elections municipales",
+            ),
+            gkg_line(
+                "https://nytimes.com/c",
+                "nytimes.com",
+                "ENV_CLIMATECHANGE",
+                "C",
+            ),
+        ]
+        .join("\n");
+        let mut writer = zip::ZipWriter::new(
+            File::create(directory.join("20230617170000.gkg.csv.zip")).unwrap(),
+        );
+        writer
+            .start_file(
+                "20230617170000.gkg.csv",
+                zip::write::FileOptions::default()
+                    .compression_method(zip::CompressionMethod::Stored),
+            )
+            .unwrap();
+        writer.write_all(zipped.as_bytes()).unwrap();
+        writer.finish().unwrap();
+
+        let archive = GDeltaFilesNewsArchive::new(
+            Box::new(crate::adapters::logger_slog::SlogLoggerAdapter::new()),
+            directory.clone(),
+        );
+        let (channel, mut rx) = mpsc::channel(10);
+        let filter = filter();
+        let files_read = archive
+            .read_articles(filter.countries, filter.categories, channel)
+            .await
+            .unwrap();
+        let mut titles = Vec::new();
+        while let Some(articles) = rx.recv().await {
+            titles.extend(articles.into_iter().map(|article| article.title));
+        }
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(files_read, 2);
+        assert_eq!(titles, vec!["A", "Elections municipales"]);
+    }
+
+    #[tokio::test]
+    async fn test_read_articles_stops_once_receiver_is_dropped() {
+        let directory =
+            std::env::temp_dir().join(format!("gdelt_export_closed_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        for (file, url) in [
+            ("20230617164500.gkg.csv", "https://lemonde.fr/a"),
+            ("20230617170000.gkg.csv", "https://lemonde.fr/b"),
+        ] {
+            let line = gkg_line(url, "lemonde.fr", "ENV_CLIMATECHANGE", "A");
+            std::fs::write(directory.join(file), line).unwrap();
+        }
+
+        let archive = GDeltaFilesNewsArchive::new(
+            Box::new(crate::adapters::logger_slog::SlogLoggerAdapter::new()),
+            directory.clone(),
+        );
+        let (channel, rx) = mpsc::channel(10);
+        drop(rx);
+        let filter = filter();
+        let result = archive
+            .read_articles(filter.countries, filter.categories, channel)
+            .await;
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert!(matches!(result, Err(ArchiveError::Closed)));
+    }
+}
@@ -1,10 +1,12 @@
+use crate::adapters::postgres_error::is_foreign_key_violation;
 use crate::core::ports::NewsRepository;
+use crate::core::ports::RepositoryError;
 use crate::core::{domain, ports};
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use isocountry::CountryCode;
 use sqlx::postgres::PgRow;
-use sqlx::{PgPool, Postgres, Row, Transaction};
-use std::error::Error;
+use sqlx::{PgPool, Postgres, QueryBuilder, Row, Transaction};
 
 pub struct PostgresNewsRepository {
     pool: PgPool,
@@ -23,68 +25,93 @@ impl NewsRepository for PostgresNewsRepository {
         &self,
         categories: Vec<String>,
         date_range: domain::DateRange,
-    ) -> Result<Vec<domain::NewsArticle>, Box<dyn Error>> {
-        let rows = sqlx::query(
-            r#"
-                SELECT news_articles.*, news_article_categories.category_name
-                FROM news_articles
-                JOIN news_article_categories ON news_articles.id = news_article_categories.news_article_id
-                WHERE news_article_categories.category_name = ANY($1)
-                AND news_articles.seen_at >= $2
-                AND news_articles.seen_at <= $3
-                "#,
+        page: domain::PageRequest,
+    ) -> Result<domain::ArticlePage, RepositoryError> {
+        self.search_articles(
+            categories,
+            date_range,
+            domain::ArticleFilter::default(),
+            page,
         )
-        .bind(&categories)
-        .bind(date_range.inclusive_start_date)
-        .bind(date_range.inclusive_end_date)
-        .fetch_all(&self.pool)
-        .await?;
+        .await
+    }
+
+    async fn search_articles(
+        &self,
+        categories: Vec<String>,
+        date_range: domain::DateRange,
+        filter: domain::ArticleFilter,
+        page: domain::PageRequest,
+    ) -> Result<domain::ArticlePage, RepositoryError> {
+        let limit = page.limit;
+        let mut rows = articles_query(categories, date_range, filter, page)
+            .build()
+            .fetch_all(&self.pool)
+            .await?;
+
+        let next_cursor = if rows.len() as i64 > limit {
+            rows.truncate(limit as usize);
+            rows.last().map(|row| domain::ArticleCursor {
+                seen_at: row.get("seen_at"),
+                id: row.get("id"),
+            })
+        } else {
+            None
+        };
 
-        // TODO make this into a function and add a unit test
         let articles = rows
             .into_iter()
             .map(|row| {
-                let country_str: String = row.get("country_iso_alpha_3");
-                // TODO error handling for country code
-                let country_code = CountryCode::for_alpha3(&country_str).unwrap();
-
-                domain::NewsArticle {
+                Ok(domain::NewsArticle {
+                    id: Some(row.get("id")),
                     title: row.get("title"),
-                    category: row.get("category_name"),
+                    categories: row.get("categories"),
                     domain: row.get("domain"),
-                    country: country_code,
+                    country: get_country_code(&row, "country_iso_alpha_3")?,
                     url: row.get("url"),
                     language: row.get("language"),
                     datetime: row.get("seen_at"),
-                }
+                    url_mobile: row.get("url_mobile"),
+                    social_image: row.get("social_image"),
+                })
             })
-            .collect();
+            .collect::<Result<Vec<_>, RepositoryError>>()?;
 
-        Ok(articles)
+        Ok(domain::ArticlePage {
+            articles,
+            next_cursor,
+        })
     }
 
     async fn store_articles(
         &self,
         articles: Vec<domain::NewsArticle>,
-    ) -> Result<i32, Box<dyn std::error::Error>> {
+    ) -> Result<domain::StoredArticles, RepositoryError> {
         let mut tx = self.pool.begin().await?;
-        let mut num_inserted = 0;
+        let mut stored = domain::StoredArticles::default();
 
         for article in &articles {
-            match insert_article(&mut tx, article, &self.logger).await {
-                Ok(id) => {
-                    // If there was no error then attempt to add the category to the article
+            match insert_article(&mut tx, article, self.logger.as_ref()).await {
+                Ok((id, is_new)) => {
+                    // If there was no error then attempt to add the categories to the article,
+                    // an article stored before keeps the ones it already had
                     sqlx::query(
-                        "INSERT INTO news_article_categories (news_article_id, category_name) 
-                    VALUES ($1, $2) ON CONFLICT DO NOTHING",
+                        "INSERT INTO news_article_categories (news_article_id, category_name)
+                        SELECT $1, category_name FROM UNNEST($2::text[]) AS category_name
+                        ON CONFLICT DO NOTHING",
                     )
                     .bind(id)
-                    .bind(&article.category)
+                    .bind(&article.categories)
                     .execute(&mut tx)
                     .await?;
-                    num_inserted += 1;
+                    if is_new {
+                        stored.inserted += 1;
+                    } else {
+                        stored.duplicates += 1;
+                    }
                 }
                 Err(e) => {
+                    stored.failed += 1;
                     self.logger.error(
                         format!(
                             "Error inserting article: \n error: {} \n article: {}",
@@ -95,28 +122,229 @@ impl NewsRepository for PostgresNewsRepository {
                 }
             }
         }
-        self.logger
-            .debug(format!("Attempting to insert {} articles", num_inserted).as_str());
+        self.logger.debug(
+            format!(
+                "Inserted {} articles, {} were already stored",
+                stored.inserted, stored.duplicates
+            )
+            .as_str(),
+        );
 
         tx.commit().await?;
 
-        Ok(num_inserted)
+        Ok(stored)
     }
 
-    async fn add_category(&self, category: String) -> Result<bool, Box<dyn std::error::Error>> {
-        let result =
-            sqlx::query("INSERT INTO categories (name) VALUES ($1) ON CONFLICT (name) DO NOTHING")
+    async fn store_sync_report(&self, report: &domain::SyncReport) -> Result<i32, RepositoryError> {
+        let mut tx = self.pool.begin().await?;
+        let (id,): (i32,) = sqlx::query_as(
+            "INSERT INTO sync_runs
+            (mode, started_at, finished_at, fetched, inserted, duplicates, failed_windows, failed_queries,
+            inclusive_start_date, inclusive_end_date)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) RETURNING id",
+        )
+        .bind(report.mode.as_str())
+        .bind(report.started_at)
+        .bind(report.finished_at)
+        .bind(report.fetched())
+        .bind(report.inserted())
+        .bind(report.duplicates())
+        .bind(report.failed_windows())
+        .bind(report.failed_queries().count() as i32)
+        .bind(report.inclusive_start_date)
+        .bind(report.inclusive_end_date)
+        .fetch_one(&mut tx)
+        .await?;
+
+        for query in &report.queries {
+            sqlx::query(
+                "INSERT INTO sync_run_queries
+                (sync_run_id, country_iso_alpha_3, category_name, inclusive_start_date, inclusive_end_date,
+                fetched, inserted, duplicates, failed_windows, errors, duration_ms)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)",
+            )
+            .bind(id)
+            .bind(query.country.alpha3())
+            .bind(&query.category)
+            .bind(query.inclusive_start_date)
+            .bind(query.inclusive_end_date)
+            .bind(query.fetched)
+            .bind(query.inserted)
+            .bind(query.duplicates)
+            .bind(query.failed_windows)
+            .bind(&query.errors)
+            .bind(query.duration_ms)
+            .execute(&mut tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    async fn get_sync_runs(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<domain::SyncRunSummary>, RepositoryError> {
+        let rows = sqlx::query("SELECT * FROM sync_runs ORDER BY started_at DESC LIMIT $1")
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(to_sync_run_summary).collect()
+    }
+
+    async fn get_sync_report(
+        &self,
+        id: i32,
+    ) -> Result<Option<domain::SyncReport>, RepositoryError> {
+        let run = match sqlx::query("SELECT * FROM sync_runs WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+        {
+            Some(row) => to_sync_run_summary(&row)?,
+            None => return Ok(None),
+        };
+        let rows = sqlx::query(
+            "SELECT * FROM sync_run_queries WHERE sync_run_id = $1
+            ORDER BY country_iso_alpha_3, category_name",
+        )
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await?;
+        let queries = rows
+            .iter()
+            .map(|row| {
+                Ok(domain::QueryReport {
+                    country: get_country_code(row, "country_iso_alpha_3")?,
+                    category: row.get("category_name"),
+                    inclusive_start_date: row.get("inclusive_start_date"),
+                    inclusive_end_date: row.get("inclusive_end_date"),
+                    fetched: row.get("fetched"),
+                    inserted: row.get("inserted"),
+                    duplicates: row.get("duplicates"),
+                    failed_windows: row.get("failed_windows"),
+                    errors: row.get("errors"),
+                    duration_ms: row.get("duration_ms"),
+                })
+            })
+            .collect::<Result<Vec<domain::QueryReport>, RepositoryError>>()?;
+
+        Ok(Some(domain::SyncReport {
+            id: Some(run.id),
+            mode: run.mode,
+            started_at: run.started_at,
+            finished_at: run.finished_at,
+            inclusive_start_date: run.inclusive_start_date,
+            inclusive_end_date: run.inclusive_end_date,
+            queries,
+        }))
+    }
+
+    async fn get_sync_statuses(&self) -> Result<Vec<domain::SyncStatus>, RepositoryError> {
+        let rows = sqlx::query(
+            r#"
+                SELECT COALESCE(last_success.country_iso_alpha_3, sync_state.country_iso_alpha_3) AS country_iso_alpha_3,
+                    COALESCE(last_success.category_name, sync_state.category_name) AS category_name,
+                    last_success.finished_at AS last_success_at,
+                    sync_state.synced_until
+                FROM (
+                    SELECT DISTINCT ON (sync_run_queries.country_iso_alpha_3, sync_run_queries.category_name)
+                        sync_run_queries.country_iso_alpha_3, sync_run_queries.category_name, sync_runs.finished_at
+                    FROM sync_run_queries
+                    JOIN sync_runs ON sync_runs.id = sync_run_queries.sync_run_id
+                    WHERE sync_run_queries.failed_windows = 0 AND cardinality(sync_run_queries.errors) = 0
+                    ORDER BY sync_run_queries.country_iso_alpha_3, sync_run_queries.category_name, sync_runs.finished_at DESC
+                ) AS last_success
+                FULL OUTER JOIN sync_state
+                ON last_success.country_iso_alpha_3 = sync_state.country_iso_alpha_3
+                AND last_success.category_name = sync_state.category_name
+                "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(domain::SyncStatus {
+                    last_success_at: row.get("last_success_at"),
+                    synced_until: row.get("synced_until"),
+                    ..domain::SyncStatus::new(
+                        get_country_code(row, "country_iso_alpha_3")?,
+                        row.get("category_name"),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    async fn add_category(
+        &self,
+        category: String,
+        expression: Option<String>,
+    ) -> Result<bool, RepositoryError> {
+        let result = sqlx::query(
+            "INSERT INTO categories (name, expression) VALUES ($1, $2) ON CONFLICT (name) DO NOTHING",
+        )
+        .bind(&category)
+        .bind(&expression)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_category_expression(
+        &self,
+        category: String,
+    ) -> Result<Option<String>, RepositoryError> {
+        let row: Option<(Option<String>,)> =
+            sqlx::query_as("SELECT expression FROM categories WHERE name = $1")
                 .bind(&category)
-                .execute(&self.pool)
+                .fetch_optional(&self.pool)
                 .await?;
 
-        Ok(result.rows_affected() > 0)
+        Ok(row.and_then(|row| row.0))
     }
 
-    async fn is_valid_category(
+    async fn get_article_titles(
+        &self,
+        date_range: domain::DateRange,
+    ) -> Result<Vec<(i32, String)>, RepositoryError> {
+        let rows = sqlx::query(
+            "SELECT id, title FROM news_articles
+            WHERE seen_at >= $1 AND seen_at <= $2 AND title IS NOT NULL",
+        )
+        .bind(date_range.inclusive_start_date)
+        .bind(date_range.inclusive_end_date)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get("id"), row.get("title")))
+            .collect())
+    }
+
+    async fn add_article_categories(
         &self,
         category: String,
-    ) -> Result<bool, Box<dyn std::error::Error>> {
+        article_ids: Vec<i32>,
+    ) -> Result<i32, RepositoryError> {
+        let result = sqlx::query(
+            "INSERT INTO news_article_categories (news_article_id, category_name)
+            SELECT id, $1 FROM UNNEST($2::int[]) AS id
+            ON CONFLICT DO NOTHING",
+        )
+        .bind(&category)
+        .bind(&article_ids)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() as i32)
+    }
+
+    async fn is_valid_category(&self, category: String) -> Result<bool, RepositoryError> {
         let row: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM categories WHERE name = $1")
             .bind(&category)
             .fetch_one(&self.pool)
@@ -125,7 +353,7 @@ impl NewsRepository for PostgresNewsRepository {
         Ok(row.0 > 0)
     }
 
-    async fn get_categories(&self) -> Result<Vec<String>, Box<dyn Error>> {
+    async fn get_categories(&self) -> Result<Vec<String>, RepositoryError> {
         let rows = sqlx::query("SELECT name FROM categories")
             .fetch_all(&self.pool)
             .await?;
@@ -133,7 +361,140 @@ impl NewsRepository for PostgresNewsRepository {
         Ok(categories)
     }
 
-    async fn get_countries(&self) -> Result<Vec<CountryCode>, Box<dyn Error>> {
+    async fn get_category_definitions(&self) -> Result<Vec<domain::Category>, RepositoryError> {
+        let rows = sqlx::query("SELECT name, expression FROM categories ORDER BY name")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| domain::Category {
+                name: row.get("name"),
+                expression: row.get("expression"),
+            })
+            .collect())
+    }
+
+    // The tables referencing the category follow through ON UPDATE CASCADE, the sync runs
+    // keep the name the category had when they ran
+    async fn rename_category(
+        &self,
+        category: String,
+        new_name: String,
+    ) -> Result<Option<domain::Category>, RepositoryError> {
+        let row = sqlx::query(
+            "UPDATE categories SET name = $2 WHERE name = $1 RETURNING name, expression",
+        )
+        .bind(&category)
+        .bind(&new_name)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| domain::Category {
+            name: row.get("name"),
+            expression: row.get("expression"),
+        }))
+    }
+
+    async fn delete_category(
+        &self,
+        category: String,
+        purge_articles: bool,
+    ) -> Result<Option<i32>, RepositoryError> {
+        let mut tx = self.pool.begin().await?;
+        let untagged: Vec<(i32,)> = sqlx::query_as(
+            "DELETE FROM news_article_categories WHERE category_name = $1
+            RETURNING news_article_id",
+        )
+        .bind(&category)
+        .fetch_all(&mut tx)
+        .await?;
+
+        let mut num_deleted = 0;
+        if purge_articles && !untagged.is_empty() {
+            let article_ids: Vec<i32> = untagged.into_iter().map(|row| row.0).collect();
+            let result = sqlx::query(
+                "DELETE FROM news_articles WHERE id = ANY($1)
+                AND NOT EXISTS (
+                    SELECT 1 FROM news_article_categories
+                    WHERE news_article_categories.news_article_id = news_articles.id
+                )",
+            )
+            .bind(&article_ids)
+            .execute(&mut tx)
+            .await?;
+            num_deleted = result.rows_affected() as i32;
+        }
+
+        let result = sqlx::query("DELETE FROM categories WHERE name = $1")
+            .bind(&category)
+            .execute(&mut tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Ok(None);
+        }
+        tx.commit().await?;
+
+        Ok(Some(num_deleted))
+    }
+
+    async fn add_category_keyword(
+        &self,
+        keyword: domain::CategoryKeyword,
+    ) -> Result<bool, RepositoryError> {
+        let result = sqlx::query(
+            "INSERT INTO category_keywords (category_name, phrase, language) VALUES ($1, $2, $3)
+            ON CONFLICT (category_name, phrase) DO NOTHING",
+        )
+        .bind(&keyword.category)
+        .bind(&keyword.phrase)
+        .bind(&keyword.language)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_category_keywords(
+        &self,
+        category: String,
+    ) -> Result<Vec<domain::CategoryKeyword>, RepositoryError> {
+        let rows = sqlx::query(
+            "SELECT category_name, phrase, language FROM category_keywords
+            WHERE category_name = $1 ORDER BY phrase",
+        )
+        .bind(&category)
+        .fetch_all(&self.pool)
+        .await?;
+        let keywords = rows
+            .into_iter()
+            .map(|row| {
+                domain::CategoryKeyword::new(
+                    row.get("category_name"),
+                    row.get("phrase"),
+                    row.get("language"),
+                )
+            })
+            .collect();
+        Ok(keywords)
+    }
+
+    async fn remove_category_keyword(
+        &self,
+        category: String,
+        phrase: String,
+    ) -> Result<bool, RepositoryError> {
+        let result =
+            sqlx::query("DELETE FROM category_keywords WHERE category_name = $1 AND phrase = $2")
+                .bind(&category)
+                .bind(&phrase)
+                .execute(&self.pool)
+                .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_countries(&self) -> Result<Vec<CountryCode>, RepositoryError> {
         let rows = sqlx::query("SELECT iso_alpha_3 from countries")
             .fetch_all(&self.pool)
             .await?;
@@ -143,28 +504,283 @@ impl NewsRepository for PostgresNewsRepository {
             .collect();
         Ok(countries)
     }
+
+    async fn add_country(&self, country: CountryCode) -> Result<bool, RepositoryError> {
+        let result = sqlx::query(
+            "INSERT INTO countries (iso_alpha_3) VALUES ($1) ON CONFLICT (iso_alpha_3) DO NOTHING",
+        )
+        .bind(country.alpha3())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // The sync state and timelines go through ON DELETE CASCADE, the articles' foreign key
+    // makes the delete fail with a Conflict unless they were purged first
+    async fn remove_country(
+        &self,
+        country: CountryCode,
+        purge_articles: bool,
+    ) -> Result<Option<i32>, RepositoryError> {
+        let mut tx = self.pool.begin().await?;
+        let mut num_deleted = 0;
+        if purge_articles {
+            sqlx::query(
+                "DELETE FROM news_article_categories WHERE news_article_id IN (
+                    SELECT id FROM news_articles WHERE country_iso_alpha_3 = $1
+                )",
+            )
+            .bind(country.alpha3())
+            .execute(&mut tx)
+            .await?;
+            let result = sqlx::query("DELETE FROM news_articles WHERE country_iso_alpha_3 = $1")
+                .bind(country.alpha3())
+                .execute(&mut tx)
+                .await?;
+            num_deleted = result.rows_affected() as i32;
+        }
+        // Jobs already claimed are left to fail on their own
+        sqlx::query("DELETE FROM sync_jobs WHERE country_iso_alpha_3 = $1 AND status = 'queued'")
+            .bind(country.alpha3())
+            .execute(&mut tx)
+            .await?;
+
+        let result = sqlx::query("DELETE FROM countries WHERE iso_alpha_3 = $1")
+            .bind(country.alpha3())
+            .execute(&mut tx)
+            .await
+            .map_err(|e| {
+                if is_foreign_key_violation(&e) {
+                    RepositoryError::Conflict(e.to_string())
+                } else {
+                    e.into()
+                }
+            })?;
+        if result.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Ok(None);
+        }
+        tx.commit().await?;
+
+        Ok(Some(num_deleted))
+    }
+
+    async fn get_sync_watermark(
+        &self,
+        country: CountryCode,
+        category: String,
+    ) -> Result<Option<domain::SyncWatermark>, RepositoryError> {
+        let row: Option<(DateTime<Utc>,)> = sqlx::query_as(
+            "SELECT synced_until FROM sync_state
+            WHERE country_iso_alpha_3 = $1 AND category_name = $2",
+        )
+        .bind(country.alpha3())
+        .bind(&category)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|(synced_until,)| domain::SyncWatermark {
+            country,
+            category,
+            synced_until,
+        }))
+    }
+
+    async fn set_sync_watermark(
+        &self,
+        watermark: &domain::SyncWatermark,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO sync_state (country_iso_alpha_3, category_name, synced_until)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (country_iso_alpha_3, category_name) DO UPDATE
+            SET synced_until = GREATEST(sync_state.synced_until, EXCLUDED.synced_until),
+                updated_at = now()",
+        )
+        .bind(watermark.country.alpha3())
+        .bind(&watermark.category)
+        .bind(watermark.synced_until)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn store_coverage_timeline(
+        &self,
+        timeline: &domain::CoverageTimeline,
+    ) -> Result<i32, RepositoryError> {
+        let mut tx = self.pool.begin().await?;
+        let mut num_stored = 0;
+        for point in &timeline.points {
+            sqlx::query(
+                "INSERT INTO coverage_timeline (country_iso_alpha_3, category_name, mode, bucket_start, value, total)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT (country_iso_alpha_3, category_name, mode, bucket_start)
+                DO UPDATE SET value = EXCLUDED.value, total = EXCLUDED.total",
+            )
+            .bind(timeline.country.alpha3())
+            .bind(&timeline.category)
+            .bind(timeline.mode.as_str())
+            .bind(point.datetime)
+            .bind(point.value)
+            .bind(point.total)
+            .execute(&mut tx)
+            .await?;
+            num_stored += 1;
+        }
+        tx.commit().await?;
+        self.logger
+            .debug(format!("Stored {} coverage points", num_stored).as_str());
+        Ok(num_stored)
+    }
+
+    async fn get_coverage_timeline(
+        &self,
+        country: CountryCode,
+        category: String,
+        mode: domain::CoverageMode,
+        date_range: domain::DateRange,
+    ) -> Result<domain::CoverageTimeline, RepositoryError> {
+        let rows = sqlx::query(
+            r#"
+                SELECT bucket_start, value, total
+                FROM coverage_timeline
+                WHERE country_iso_alpha_3 = $1
+                AND category_name = $2
+                AND mode = $3
+                AND bucket_start >= $4
+                AND bucket_start <= $5
+                ORDER BY bucket_start
+                "#,
+        )
+        .bind(country.alpha3())
+        .bind(&category)
+        .bind(mode.as_str())
+        .bind(date_range.inclusive_start_date)
+        .bind(date_range.inclusive_end_date)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let points = rows
+            .into_iter()
+            .map(|row| domain::CoveragePoint {
+                datetime: row.get("bucket_start"),
+                value: row.get("value"),
+                total: row.get("total"),
+            })
+            .collect();
+
+        Ok(domain::CoverageTimeline {
+            country,
+            category,
+            mode,
+            points,
+        })
+    }
+
+    async fn store_tone_timeline(
+        &self,
+        timeline: &domain::ToneTimeline,
+    ) -> Result<i32, RepositoryError> {
+        let mut tx = self.pool.begin().await?;
+        let mut num_stored = 0;
+        for point in &timeline.points {
+            sqlx::query(
+                "INSERT INTO tone_timeline (country_iso_alpha_3, category_name, bucket_start, average_tone)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (country_iso_alpha_3, category_name, bucket_start)
+                DO UPDATE SET average_tone = EXCLUDED.average_tone",
+            )
+            .bind(timeline.country.alpha3())
+            .bind(&timeline.category)
+            .bind(point.datetime)
+            .bind(point.average_tone)
+            .execute(&mut tx)
+            .await?;
+            num_stored += 1;
+        }
+        tx.commit().await?;
+        self.logger
+            .debug(format!("Stored {} tone points", num_stored).as_str());
+        Ok(num_stored)
+    }
+
+    async fn get_tone_timelines(
+        &self,
+        categories: Vec<String>,
+        date_range: domain::DateRange,
+    ) -> Result<Vec<domain::ToneTimeline>, RepositoryError> {
+        let rows = sqlx::query(
+            r#"
+                SELECT country_iso_alpha_3, category_name, bucket_start, average_tone
+                FROM tone_timeline
+                WHERE category_name = ANY($1)
+                AND bucket_start >= $2
+                AND bucket_start <= $3
+                ORDER BY country_iso_alpha_3, category_name, bucket_start
+                "#,
+        )
+        .bind(&categories)
+        .bind(date_range.inclusive_start_date)
+        .bind(date_range.inclusive_end_date)
+        .fetch_all(&self.pool)
+        .await?;
+
+        // Rows are ordered so the points of a timeline are next to each other
+        let mut timelines: Vec<domain::ToneTimeline> = Vec::new();
+        for row in rows {
+            let country = get_country_code(&row, "country_iso_alpha_3")?;
+            let category: String = row.get("category_name");
+            let point = domain::TonePoint {
+                datetime: row.get("bucket_start"),
+                average_tone: row.get("average_tone"),
+            };
+            match timelines.last_mut() {
+                Some(timeline) if timeline.country == country && timeline.category == category => {
+                    timeline.points.push(point)
+                }
+                _ => timelines.push(domain::ToneTimeline {
+                    country,
+                    category,
+                    points: vec![point],
+                }),
+            }
+        }
+        Ok(timelines)
+    }
 }
 
 async fn insert_article(
     tx: &mut Transaction<'_, Postgres>,
     article: &domain::NewsArticle,
-    logger: &Box<dyn ports::Logger>,
-) -> Result<i32, sqlx::Error> {
+    logger: &dyn ports::Logger,
+) -> Result<(i32, bool), sqlx::Error> {
     match sqlx::query_as(
-        "INSERT INTO news_articles (title, domain, country_iso_alpha_3, seen_at, url, language) 
-        VALUES ($1, $2, $3, $4, $5, $6) 
-        ON CONFLICT (title, domain, country_iso_alpha_3, seen_at) DO NOTHING RETURNING id",
+        "INSERT INTO news_articles (title, domain, country_iso_alpha_3, seen_at, url, language, url_mobile, social_image)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT (title, domain, country_iso_alpha_3, seen_at) DO UPDATE SET
+            url_mobile = COALESCE(news_articles.url_mobile, EXCLUDED.url_mobile),
+            social_image = COALESCE(news_articles.social_image, EXCLUDED.social_image)
+        -- Only touch existing rows when there is media to backfill
+        WHERE (news_articles.url_mobile IS NULL AND EXCLUDED.url_mobile IS NOT NULL)
+            OR (news_articles.social_image IS NULL AND EXCLUDED.social_image IS NOT NULL)
+        RETURNING id, (xmax = 0) AS inserted",
     )
     .bind(&article.title)
     .bind(&article.domain)
     .bind(article.country.alpha3())
-    .bind(&article.datetime)
+    .bind(article.datetime)
     .bind(&article.url)
     .bind(&article.language)
+    .bind(&article.url_mobile)
+    .bind(&article.social_image)
     .fetch_optional(&mut *tx)
     .await?
     {
-        Some((id,)) => Ok(id),
+        // xmax is zero only for freshly inserted rows, not for backfilled ones
+        Some((id, inserted)) => Ok((id, inserted)),
         None => {
             logger.debug(format!("Article already exists: {}", &article.title).as_str());
             let id: (i32,) = sqlx::query_as(
@@ -174,27 +790,178 @@ async fn insert_article(
             .bind(&article.title)
             .bind(&article.domain)
             .bind(article.country.alpha3())
-            .bind(&article.datetime)
+            .bind(article.datetime)
             .fetch_one(&mut *tx)
             .await?;
             // Return the id of the existing article
-            Ok(id.0)
+            Ok((id.0, false))
         }
     }
 }
 
-fn get_country_code(row: &PgRow, field_name: &str) -> Result<CountryCode, Box<dyn Error>> {
-    let country_str: String = row.get(field_name);
-    CountryCode::for_alpha3(&country_str).map_err(|_| Box::new(CountryCodeError) as Box<dyn Error>)
+fn to_sync_run_summary(row: &PgRow) -> Result<domain::SyncRunSummary, RepositoryError> {
+    let mode: String = row.get("mode");
+    let started_at: DateTime<Utc> = row.get("started_at");
+    let finished_at: DateTime<Utc> = row.get("finished_at");
+    Ok(domain::SyncRunSummary {
+        id: row.get("id"),
+        mode: domain::SyncMode::from_name(&mode)
+            .ok_or_else(|| RepositoryError::InvalidData(format!("Unknown sync mode {}", mode)))?,
+        started_at,
+        finished_at,
+        // Runs stored before the date range was recorded fall back to when they ran
+        inclusive_start_date: row
+            .get::<Option<DateTime<Utc>>, _>("inclusive_start_date")
+            .unwrap_or(started_at),
+        inclusive_end_date: row
+            .get::<Option<DateTime<Utc>>, _>("inclusive_end_date")
+            .unwrap_or(finished_at),
+        fetched: row.get("fetched"),
+        inserted: row.get("inserted"),
+        duplicates: row.get("duplicates"),
+        failed_windows: row.get("failed_windows"),
+        failed_queries: row.get("failed_queries"),
+    })
 }
 
-#[derive(Debug)]
-struct CountryCodeError;
+// Every value from the request is bound as a parameter, only the sort direction, which comes
+// from an enum, is written into the SQL. One more row than the page holds is asked for to tell
+// whether there is a next page.
+fn articles_query(
+    categories: Vec<String>,
+    date_range: domain::DateRange,
+    filter: domain::ArticleFilter,
+    page: domain::PageRequest,
+) -> QueryBuilder<'static, Postgres> {
+    // Articles having one of the categories come with all of their categories
+    let mut query = QueryBuilder::new(
+        "SELECT news_articles.*,
+            array_agg(news_article_categories.category_name ORDER BY news_article_categories.category_name)
+                AS categories
+        FROM news_articles
+        JOIN news_article_categories ON news_articles.id = news_article_categories.news_article_id
+        WHERE news_articles.id IN (
+            SELECT news_article_id FROM news_article_categories WHERE category_name = ANY(",
+    );
+    query.push_bind(categories);
+    query.push(")) AND news_articles.seen_at >= ");
+    query.push_bind(date_range.inclusive_start_date);
+    query.push(" AND news_articles.seen_at <= ");
+    query.push_bind(date_range.inclusive_end_date);
+
+    if !filter.countries.is_empty() {
+        let countries: Vec<String> = filter
+            .countries
+            .iter()
+            .map(|country| country.alpha3().to_string())
+            .collect();
+        query.push(" AND news_articles.country_iso_alpha_3 = ANY(");
+        query.push_bind(countries);
+        query.push(")");
+    }
+    if !filter.languages.is_empty() {
+        query.push(" AND lower(news_articles.language) = ANY(");
+        query.push_bind(lowercase(&filter.languages));
+        query.push(")");
+    }
+    if !filter.domains.is_empty() {
+        query.push(" AND lower(news_articles.domain) = ANY(");
+        query.push_bind(lowercase(&filter.domains));
+        query.push(")");
+    }
+    if !filter.excluded_domains.is_empty() {
+        // A NULL domain isn't one of the excluded ones
+        query.push(" AND (news_articles.domain IS NULL OR lower(news_articles.domain) <> ALL(");
+        query.push_bind(lowercase(&filter.excluded_domains));
+        query.push("))");
+    }
+    if let Some(text) = &filter.title_contains {
+        query.push(" AND news_articles.title ILIKE ");
+        query.push_bind(format!("%{}%", escape_like(text)));
+    }
 
-impl std::fmt::Display for CountryCodeError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Invalid country code")
+    let (direction, after) = match page.order {
+        domain::SortOrder::Asc => ("ASC", ">"),
+        domain::SortOrder::Desc => ("DESC", "<"),
+    };
+    if let Some(cursor) = page.after {
+        // Compared as a row so the (seen_at, id) index can serve the next page
+        query.push(format!(
+            " AND (news_articles.seen_at, news_articles.id) {} (",
+            after
+        ));
+        query.push_bind(cursor.seen_at);
+        query.push(", ");
+        query.push_bind(cursor.id);
+        query.push(")");
     }
+    query.push(format!(
+        " GROUP BY news_articles.id \
+        ORDER BY news_articles.seen_at {direction}, news_articles.id {direction} LIMIT "
+    ));
+    query.push_bind(page.limit + 1);
+    query
+}
+
+fn lowercase(values: &[String]) -> Vec<String> {
+    values.iter().map(|value| value.to_lowercase()).collect()
+}
+
+// Makes the wildcards of LIKE patterns match themselves
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+pub(crate) fn get_country_code(
+    row: &PgRow,
+    field_name: &str,
+) -> Result<CountryCode, RepositoryError> {
+    let country_str: String = row.get(field_name);
+    CountryCode::for_alpha3(&country_str)
+        .map_err(|_| RepositoryError::InvalidData(format!("Invalid country code {}", country_str)))
 }
 
-impl std::error::Error for CountryCodeError {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_articles_query_binds_the_filter() {
+        let start = Utc.with_ymd_and_hms(2023, 6, 1, 0, 0, 0).unwrap();
+        let filter = domain::ArticleFilter {
+            countries: vec![CountryCode::FRA],
+            languages: vec!["French".to_string()],
+            domains: vec![],
+            excluded_domains: vec!["spam.com".to_string()],
+            title_contains: Some("'; DROP TABLE news_articles; --".to_string()),
+        };
+        let page = domain::PageRequest {
+            limit: 10,
+            order: domain::SortOrder::Asc,
+            after: None,
+        };
+        let query = articles_query(
+            vec!["heat wave".to_string()],
+            domain::DateRange::new(start, start + chrono::Duration::days(1)).unwrap(),
+            filter,
+            page,
+        );
+
+        let sql = query.sql();
+        assert!(!sql.contains("DROP TABLE"));
+        assert!(!sql.contains("spam.com"));
+        assert!(sql.contains("country_iso_alpha_3 = ANY($4)"));
+        assert!(!sql.contains("lower(news_articles.domain) = ANY"));
+        assert!(sql.contains("title ILIKE $7"));
+        assert!(sql.contains("seen_at ASC"));
+        assert!(sql.ends_with("LIMIT $8"));
+    }
+
+    #[test]
+    fn test_escape_like() {
+        assert_eq!(escape_like("100% off_line\\"), "100\\% off\\_line\\\\");
+    }
+}
@@ -1,21 +1,55 @@
-use crate::core::domain::{ArticleQuery, NewsArticle};
+use crate::adapters::gdeltproject_countries;
+use crate::adapters::gdeltproject_scheduler::{RequestScheduler, RequestSchedulerConfig};
+use crate::core::domain::{
+    ArticleQuery, CategoryExpression, CoverageMode, CoveragePoint, DateRange, NewsArticle,
+    SearchOutcome, TonePoint, WindowStatus,
+};
 use crate::core::ports;
-use crate::core::ports::NewsSearchClient;
+use crate::core::ports::{NewsSearchClient, SearchClientError};
 use async_trait::async_trait;
 use chrono::format::ParseError;
-use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use chrono::{DateTime, NaiveDateTime, Utc};
 use isocountry::CountryCode;
-use std::collections::HashMap;
-use std::error::Error;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 use tokio::sync::mpsc;
 use urlencoding::encode;
 
+pub const DEFAULT_BASE_URL: &str = "https://api.gdeltproject.org";
+// The maximum number of articles the api returns for a single request
+const MAX_RECORDS: usize = 250;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+// Guards against the api streaming an unexpectedly large body into memory
+const MAX_RESPONSE_BYTES: usize = 32 * 1024 * 1024;
+// The api answers with this text instead of json when it is called too often
+const THROTTLE_MESSAGE: &str = "Please limit requests";
+
 pub struct GDeltaProjectNewsSearchAdapter {
     logger: Box<dyn ports::Logger>,
+    // Scheme and host of the api, e.g. DEFAULT_BASE_URL
+    base_url: String,
+    // Shared between requests so connections to the api are kept alive and reused
+    http_client: reqwest::Client,
+    scheduler: RequestScheduler,
 }
 impl GDeltaProjectNewsSearchAdapter {
-    pub fn new(logger: Box<dyn ports::Logger>) -> Self {
-        Self { logger }
+    pub fn new(
+        logger: Box<dyn ports::Logger>,
+        base_url: String,
+        scheduler_config: RequestSchedulerConfig,
+    ) -> Self {
+        let http_client = reqwest::Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .pool_idle_timeout(Duration::from_secs(90))
+            .build()
+            .expect("Failed to build the GDELT http client");
+        Self {
+            logger,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            http_client,
+            scheduler: RequestScheduler::new(scheduler_config),
+        }
     }
 }
 
@@ -25,12 +59,14 @@ impl NewsSearchClient for GDeltaProjectNewsSearchAdapter {
         &self,
         query: ArticleQuery,
         channel: mpsc::Sender<Vec<NewsArticle>>,
-    ) {
-        let mut start_time = query.date_range.inclusive_start_date;
+    ) -> SearchOutcome {
         self.logger.debug(
             format!(
                 "Fetching articles between {} and {}...",
-                start_time.format("%Y-%m-%d %H:%M:%S"),
+                query
+                    .date_range
+                    .inclusive_start_date
+                    .format("%Y-%m-%d %H:%M:%S"),
                 query
                     .date_range
                     .inclusive_end_date
@@ -39,221 +75,594 @@ impl NewsSearchClient for GDeltaProjectNewsSearchAdapter {
             .as_str(),
         );
 
-        while start_time < query.date_range.inclusive_end_date {
-            let resp = match self.call_url(
-                start_time,
-                query.date_range.inclusive_end_date,
-                query.source_country,
-                query.category.to_string(),
-            ) {
-                Ok(response) => response,
-                Err(err) => {
-                    self.logger
-                        .warn(format!("Error calling URL: {}", err).as_str());
-                    break; // Skip this iteration and continue with the next one.
-                }
-            };
-
-            let articles: Vec<GDeltaArticle>;
-            match extract_articles_from_response(resp) {
-                Ok(ars) => {
-                    articles = ars;
-                }
-                Err(_err) => {
-                    self.logger.warn("Error extracting articles: {err}");
-                    break;
-                }
+        let mut outcome = SearchOutcome::default();
+        let terms = match to_gdelt_query(&query.search_expression()) {
+            Ok(terms) => terms,
+            Err(err) => {
+                self.logger.error(&err.to_string());
+                outcome.record(
+                    query.date_range.clone(),
+                    WindowStatus::Failed {
+                        attempts: 0,
+                        error: err.to_string(),
+                    },
+                );
+                return outcome;
             }
-            let news_articles = to_news_article(articles, &query.category, query.source_country);
-            match news_articles.len() {
-                0 => {
-                    self.logger.debug("No articles found");
+        };
+        // The api caps every response at MAX_RECORDS articles, so whenever a window comes back
+        // full we bisect it and query both halves until the windows are small enough to be
+        // enumerated completely. The halves share their midpoint, hence the de-duplication.
+        let mut seen_urls: HashSet<String> = HashSet::new();
+        let mut windows = vec![query.date_range.clone()];
+
+        while let Some(window) = windows.pop() {
+            // The receiver is dropped when the sync is cancelled, stop requesting right away
+            // instead of finishing the window
+            let fetched = tokio::select! {
+                fetched = self.fetch_window(&query, &terms, &window) => fetched,
+                _ = channel.closed() => {
+                    self.logger
+                        .warn("Article receiver was dropped, stopping the search");
                     break;
                 }
-                1..=249 => {
-                    self.logger.debug("Less than 250 articles found");
-                    channel.send(news_articles).await.unwrap();
-                    break;
+            };
+            let articles = match fetched {
+                Ok(articles) => articles,
+                Err(status) => {
+                    outcome.record(window, status);
+                    continue;
                 }
-                // Since we hard code 250 results from the api
-                250.. => {
-                    let t = news_articles.last().unwrap().datetime;
-                    self.logger
-                        .debug(format!("Latest article date: {}", t).as_str());
-                    if t == start_time {
+            };
+            let is_saturated = articles.len() >= MAX_RECORDS;
+            let mut news_articles = to_news_article(
+                articles,
+                &query.category,
+                query.source_country,
+                self.logger.as_ref(),
+            );
+            news_articles.retain(|article| seen_urls.insert(article.url.clone()));
+
+            if is_saturated {
+                match split_window(&window) {
+                    Some((earlier, later)) => {
+                        self.logger.debug(
+                            format!(
+                                "Window {} - {} is saturated, splitting it in two",
+                                window.inclusive_start_date, window.inclusive_end_date
+                            )
+                            .as_str(),
+                        );
+                        windows.push(later);
+                        windows.push(earlier);
+                    }
+                    None => {
                         self.logger.warn(
-                            "Latest article date is the same as start_time adding one second",
+                            format!(
+                                "Window {} - {} is saturated but can not be split any further, some articles will be missing",
+                                window.inclusive_start_date, window.inclusive_end_date
+                            )
+                            .as_str(),
                         );
-                        // TODO this is a bit of a hack becase if there are more than 250 articles with the same datetime then we will never get the ones beyond 250
-                        //  There may be ways around this we will have to play with the api
-                        //  This should be logged with a warning
-                        start_time = t + chrono::Duration::seconds(1);
-                        self.logger
-                            .warn(format!("Start and end time for the articles returned from the server were the same, adding 1 second to start time.\nTime: {}", t).as_str());
-                    } else {
-                        start_time = t;
+                        outcome.record(window, WindowStatus::Saturated);
                     }
-                    self.logger
-                        .debug(format!("Latest start_time date: {}", start_time).as_str());
-                    channel.send(news_articles).await.unwrap();
-                }
-                _ => {
-                    self.logger.error("An unexpected length was returned");
-                    break;
                 }
+            } else {
+                outcome.record(window, WindowStatus::Complete);
+            }
+
+            if news_articles.is_empty() {
+                continue;
+            }
+            self.logger
+                .debug(format!("Found {} new articles", news_articles.len()).as_str());
+            if channel.send(news_articles).await.is_err() {
+                self.logger
+                    .warn("Article receiver was dropped, stopping the search");
+                break;
             }
         }
+        outcome
+    }
+
+    async fn query_coverage_timeline(
+        &self,
+        query: &ArticleQuery,
+        mode: CoverageMode,
+    ) -> Result<Vec<CoveragePoint>, SearchClientError> {
+        let terms = to_gdelt_query(&query.search_expression())?;
+        let url = build_url(
+            &self.base_url,
+            mode.into(),
+            query.date_range.inclusive_start_date,
+            query.date_range.inclusive_end_date,
+            query.source_country,
+            &terms,
+        );
+        let points = self
+            .request_with_retries(&url, parse_timeline)
+            .await
+            .map_err(|(attempts, err)| {
+                self.logger.warn(&format!(
+                    "Failed to fetch the coverage timeline after {} attempts: {}",
+                    attempts, err
+                ));
+                err
+            })?;
+        Ok(to_coverage_points(points, self.logger.as_ref()))
+    }
+
+    async fn query_tone_timeline(
+        &self,
+        query: &ArticleQuery,
+    ) -> Result<Vec<TonePoint>, SearchClientError> {
+        let terms = to_gdelt_query(&query.search_expression())?;
+        let url = build_url(
+            &self.base_url,
+            ApiMode::TimelineTone,
+            query.date_range.inclusive_start_date,
+            query.date_range.inclusive_end_date,
+            query.source_country,
+            &terms,
+        );
+        let points = self
+            .request_with_retries(&url, parse_timeline)
+            .await
+            .map_err(|(attempts, err)| {
+                self.logger.warn(&format!(
+                    "Failed to fetch the tone timeline after {} attempts: {}",
+                    attempts, err
+                ));
+                err
+            })?;
+        Ok(to_tone_points(points, self.logger.as_ref()))
+    }
+
+    fn check_expression(&self, expression: &CategoryExpression) -> Result<(), SearchClientError> {
+        to_gdelt_query(expression).map(|_| ())
     }
 }
 
 impl GDeltaProjectNewsSearchAdapter {
-    fn call_url(
+    // Requests a single window, reporting it as failed once the retries are used up
+    async fn fetch_window(
         &self,
-        start_time: DateTime<Utc>,
-        end_time: DateTime<Utc>,
-        source_country: CountryCode,
-        category: String,
-    ) -> Result<ureq::Response, Box<dyn Error>> {
-        let url = build_url(start_time, end_time, source_country, category);
-        self.logger
-            .debug(format!("Fetching articles from {}... ", url).as_str());
-        let resp = ureq::get(&url).call()?;
+        query: &ArticleQuery,
+        terms: &str,
+        window: &DateRange,
+    ) -> Result<Vec<GDeltaArticle>, WindowStatus> {
+        let url = build_url(
+            &self.base_url,
+            ApiMode::ArtList,
+            window.inclusive_start_date,
+            window.inclusive_end_date,
+            query.source_country,
+            terms,
+        );
+        self.request_with_retries(&url, parse_articles)
+            .await
+            .map_err(|(attempts, err)| {
+                self.logger.warn(
+                    format!(
+                        "Giving up on window {} - {} after {} attempts: {}",
+                        window.inclusive_start_date, window.inclusive_end_date, attempts, err
+                    )
+                    .as_str(),
+                );
+                WindowStatus::Failed {
+                    attempts,
+                    error: err.to_string(),
+                }
+            })
+    }
+
+    // Calls the url and parses the body, retrying throttled and transient failures with backoff
+    // until the scheduler's max attempts are used up. Fails with the number of attempts made.
+    async fn request_with_retries<T>(
+        &self,
+        url: &str,
+        parse: fn(&[u8]) -> Result<T, SearchClientError>,
+    ) -> Result<T, (u32, SearchClientError)> {
+        let max_attempts = self.scheduler.max_attempts();
+        let mut attempt = 1;
+        loop {
+            self.scheduler.wait_for_turn().await;
+            let result = match self.call_url(url).await {
+                Ok(response) => read_body(response).await,
+                Err(err) => Err(err),
+            }
+            .and_then(|body| parse(&body));
+
+            match result {
+                Ok(parsed) => return Ok(parsed),
+                Err(err) if err.is_retryable() && attempt < max_attempts => {
+                    let delay = self.scheduler.backoff(attempt);
+                    self.logger.warn(
+                        format!(
+                            "Attempt {} of {} failed, retrying in {:?}: {}",
+                            attempt, max_attempts, delay, err
+                        )
+                        .as_str(),
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(err) => return Err((attempt, err)),
+            }
+        }
+    }
+
+    async fn call_url(&self, url: &str) -> Result<reqwest::Response, SearchClientError> {
+        self.logger.debug(format!("Fetching {}... ", url).as_str());
+        let resp = self
+            .http_client
+            .get(url)
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+            .await?;
 
         match resp.status() {
-            200 => Ok(resp),
+            reqwest::StatusCode::OK => Ok(resp),
             status => {
-                let body = resp.into_string().unwrap();
-                Err(Box::new(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    format!("HTTP error, status: {}, body: {}", status, body),
-                )))
+                let body = resp.text().await.unwrap_or_default();
+                let message = format!("HTTP error, status: {}, body: {}", status, body);
+                if status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                    || body.contains(THROTTLE_MESSAGE)
+                {
+                    Err(SearchClientError::Throttled(message))
+                } else if status.is_server_error() {
+                    Err(SearchClientError::Transport(message))
+                } else {
+                    Err(SearchClientError::Rejected(message))
+                }
             }
         }
     }
 }
 
+impl From<reqwest::Error> for SearchClientError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() || err.is_connect() || err.is_request() || err.is_body() {
+            SearchClientError::Transport(err.to_string())
+        } else if err.is_decode() {
+            SearchClientError::Parse(err.to_string())
+        } else {
+            SearchClientError::Rejected(err.to_string())
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ApiMode {
+    ArtList,
+    TimelineVol,
+    TimelineVolRaw,
+    TimelineTone,
+}
+
+impl ApiMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ApiMode::ArtList => "artlist",
+            ApiMode::TimelineVol => "timelinevol",
+            ApiMode::TimelineVolRaw => "timelinevolraw",
+            ApiMode::TimelineTone => "timelinetone",
+        }
+    }
+}
+
+impl From<CoverageMode> for ApiMode {
+    fn from(mode: CoverageMode) -> Self {
+        match mode {
+            CoverageMode::Volume => ApiMode::TimelineVol,
+            CoverageMode::VolumeRaw => ApiMode::TimelineVolRaw,
+        }
+    }
+}
+
 #[derive(Debug, serde::Deserialize, Clone)]
 struct GDeltaArticle {
     url: String,
+    // Empty when the source has no mobile version
+    #[serde(default)]
+    url_mobile: String,
+    // The DOC API matches on machine translations of non-English articles
+    // but only ever returns the original title, so there is no translated
+    // title to store
     title: String,
     seendate: String,
+    // Empty when the article has no social image
+    #[serde(default)]
+    socialimage: String,
     domain: String,
     language: String,
     sourcecountry: String,
 }
 
+#[derive(Debug, serde::Deserialize)]
+struct GDeltaTimeline {
+    #[serde(default)]
+    timeline: Vec<GDeltaTimelineSeries>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct GDeltaTimelineSeries {
+    data: Vec<GDeltaTimelinePoint>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct GDeltaTimelinePoint {
+    date: String,
+    value: f64,
+    // Total number of monitored articles in the bucket, only part of the raw volume timeline
+    norm: Option<i64>,
+}
+
 fn build_url(
+    base_url: &str,
+    mode: ApiMode,
     start_time: DateTime<Utc>,
     end_time: DateTime<Utc>,
     source_country: CountryCode,
-    category: String,
+    terms: &str,
 ) -> String {
     let formatted_start_time = start_time.format("%Y%m%d%H%M%S").to_string();
     let formatted_end_time = end_time.format("%Y%m%d%H%M%S").to_string();
 
-    let query = format!(
-        "sourcecountry:{} AND \"{}\"",
-        source_country.alpha2(),
-        category
-    );
+    let query = format!("{} AND {}", source_country_filter(source_country), terms);
 
     let query = encode(&query);
 
     format!(
-        "https://api.gdeltproject.org/api/v2/doc/doc?query={}&mode=artlist&maxrecords=250&format=json&startdatetime={}&enddatetime={}&sort=datedesc",
-        query, formatted_start_time, formatted_end_time
+        "{}/api/v2/doc/doc?query={}&mode={}&maxrecords={}&format=json&startdatetime={}&enddatetime={}&sort=datedesc",
+        base_url, query, mode.as_str(), MAX_RECORDS, formatted_start_time, formatted_end_time
     )
 }
 
-fn extract_articles_from_response(
-    response: ureq::Response,
-) -> Result<Vec<GDeltaArticle>, Box<dyn Error>> {
-    let response_string = match response.into_string() {
-        Err(e) => {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Error reading response body: {}", e),
-            )))
-        }
-        Ok(s) => s,
-    };
+// Splits a window in two halves sharing the midpoint, returns None once the window is too small
+// to be split, the api only accepts second precision.
+fn split_window(window: &DateRange) -> Option<(DateRange, DateRange)> {
+    let span = window.inclusive_end_date - window.inclusive_start_date;
+    if span < chrono::Duration::seconds(2) {
+        return None;
+    }
+    let midpoint = window.inclusive_start_date + chrono::Duration::seconds(span.num_seconds() / 2);
+    let earlier = DateRange::new(window.inclusive_start_date, midpoint).ok()?;
+    let later = DateRange::new(midpoint, window.inclusive_end_date).ok()?;
+    Some((earlier, later))
+}
+
+fn parse_articles(body: &[u8]) -> Result<Vec<GDeltaArticle>, SearchClientError> {
+    let parsed: HashMap<String, Vec<GDeltaArticle>> = parse_json(body)?;
+    // The api returns an empty object when no articles match
+    Ok(parsed.get("articles").cloned().unwrap_or_default())
+}
+
+fn parse_timeline(body: &[u8]) -> Result<Vec<GDeltaTimelinePoint>, SearchClientError> {
+    let parsed: GDeltaTimeline = parse_json(body)?;
+    // Volume and tone timelines hold a single series
+    Ok(parsed
+        .timeline
+        .into_iter()
+        .next()
+        .map(|series| series.data)
+        .unwrap_or_default())
+}
 
-    let body: Result<HashMap<String, Vec<GDeltaArticle>>, serde_json::Error> =
-        serde_json::from_str(&response_string);
+fn parse_json<T: serde::de::DeserializeOwned>(body: &[u8]) -> Result<T, SearchClientError> {
+    serde_json::from_slice(body).map_err(|_| {
+        let body = String::from_utf8_lossy(body);
+        if body.contains(THROTTLE_MESSAGE) {
+            SearchClientError::Throttled(body.trim().to_string())
+        } else {
+            SearchClientError::Parse(body.to_string())
+        }
+    })
+}
 
-    match body {
-        Ok(body) => Ok(body["articles"].clone()),
-        Err(_) => Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("Error parsing response body: {}", response_string),
-        ))),
+// Reads the body chunk by chunk as it arrives, failing early when it grows past MAX_RESPONSE_BYTES
+async fn read_body(mut response: reqwest::Response) -> Result<Vec<u8>, SearchClientError> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| SearchClientError::Transport(format!("Error reading response body: {}", e)))?
+    {
+        if body.len() + chunk.len() > MAX_RESPONSE_BYTES {
+            return Err(SearchClientError::Parse(format!(
+                "Response body is larger than {} bytes",
+                MAX_RESPONSE_BYTES
+            )));
+        }
+        body.extend_from_slice(&chunk);
     }
+    Ok(body)
 }
 
 fn to_news_article(
     articles: Vec<GDeltaArticle>,
-    category: &String,
+    category: &str,
     source_country: CountryCode,
+    logger: &dyn ports::Logger,
 ) -> Vec<NewsArticle> {
     articles
         .iter()
         .filter_map(|element| {
             let date = to_datetime(&element.seendate);
-            if !date.is_ok() {
-                println!("Error parsing date: {}", element.seendate);
+            if date.is_err() {
+                logger.warn(&format!("Error parsing date: {}", element.seendate));
                 return None;
             }
 
-            let country = to_country(&element.sourcecountry, source_country);
-            if country.is_none() {
-                println!("Country not supported: {}", element.sourcecountry);
-                return None;
-            }
+            let country = match to_country(&element.sourcecountry, source_country) {
+                Some(country) => country,
+                None => {
+                    logger.warn(&format!("Country not supported: {}", element.sourcecountry));
+                    return None;
+                }
+            };
 
             Some(NewsArticle {
+                id: None,
                 title: element.title.clone(),
-                category: category.to_string(),
-                datetime: date.unwrap().into(),
+                categories: vec![category.to_string()],
+                datetime: date.unwrap(),
                 url: element.url.clone(),
                 domain: element.domain.clone(),
                 language: element.language.clone(),
-                country: CountryCode::FRA,
+                country,
+                url_mobile: non_empty(&element.url_mobile),
+                social_image: non_empty(&element.socialimage),
             })
         })
         .collect()
 }
 
-fn to_datetime(date: &String) -> Result<DateTime<Utc>, ParseError> {
+fn to_coverage_points(
+    points: Vec<GDeltaTimelinePoint>,
+    logger: &dyn ports::Logger,
+) -> Vec<CoveragePoint> {
+    points
+        .into_iter()
+        .filter_map(|point| match to_datetime(&point.date) {
+            Ok(datetime) => Some(CoveragePoint {
+                datetime,
+                value: point.value,
+                total: point.norm,
+            }),
+            Err(_) => {
+                logger.warn(&format!("Error parsing date: {}", point.date));
+                None
+            }
+        })
+        .collect()
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    match value.trim() {
+        "" => None,
+        value => Some(value.to_string()),
+    }
+}
+
+fn to_tone_points(points: Vec<GDeltaTimelinePoint>, logger: &dyn ports::Logger) -> Vec<TonePoint> {
+    to_coverage_points(points, logger)
+        .into_iter()
+        .map(|point| TonePoint {
+            datetime: point.datetime,
+            average_tone: point.value,
+        })
+        .collect()
+}
+
+fn to_datetime(date: &str) -> Result<DateTime<Utc>, ParseError> {
     let date = NaiveDateTime::parse_from_str(date, "%Y%m%dT%H%M%SZ")?;
     Ok(DateTime::<Utc>::from_utc(date, Utc))
 }
 
-fn to_country(country_name: &String, source_country: CountryCode) -> Option<CountryCode> {
-    if country_name == source_country.name() {
-        return Some(source_country);
+// The api only accepts FIPS codes or names without spaces for sourcecountry, countries it splits
+// into several FIPS entities are OR'd together
+fn source_country_filter(source_country: CountryCode) -> String {
+    let filters: Vec<String> = match gdeltproject_countries::to_fips_codes(source_country) {
+        codes if codes.is_empty() => vec![format!(
+            "sourcecountry:{}",
+            source_country.name().replace(' ', "").to_lowercase()
+        )],
+        codes => codes
+            .iter()
+            .map(|code| format!("sourcecountry:{}", code))
+            .collect(),
+    };
+    match filters.len() {
+        1 => filters[0].clone(),
+        _ => format!("({})", filters.join(" OR ")),
     }
-    None
+}
+
+// Compiles a category expression to the DOC api query syntax. The api only negates single
+// terms (with a leading minus) and only ORs terms inside a single level of parentheses, so
+// expressions like `NOT (a OR b)` or `(a AND b) OR c` are rejected.
+fn to_gdelt_query(expression: &CategoryExpression) -> Result<String, SearchClientError> {
+    let unsupported = || {
+        SearchClientError::Rejected(format!(
+            "The expression {:?} can not be expressed as a GDELT query",
+            expression
+        ))
+    };
+    match expression {
+        CategoryExpression::Phrase(phrase) => Ok(format!("\"{}\"", phrase.replace('"', ""))),
+        CategoryExpression::Not(operand) => match operand.as_ref() {
+            CategoryExpression::Phrase(_) => Ok(format!("-{}", to_gdelt_query(operand)?)),
+            _ => Err(unsupported()),
+        },
+        CategoryExpression::And(operands) => {
+            let terms = operands
+                .iter()
+                .map(to_gdelt_query)
+                .collect::<Result<Vec<String>, SearchClientError>>()?;
+            Ok(terms.join(" "))
+        }
+        CategoryExpression::Or(operands) => {
+            if !operands
+                .iter()
+                .all(|operand| matches!(operand, CategoryExpression::Phrase(_)))
+            {
+                return Err(unsupported());
+            }
+            let terms = operands
+                .iter()
+                .map(to_gdelt_query)
+                .collect::<Result<Vec<String>, SearchClientError>>()?;
+            Ok(format!("({})", terms.join(" OR ")))
+        }
+    }
+}
+
+// Maps the sourcecountry of an article, which the api reports by name, and checks it is the
+// country that was queried for
+fn to_country(country_name: &str, source_country: CountryCode) -> Option<CountryCode> {
+    gdeltproject_countries::from_gdelt_name(country_name)
+        .filter(|country| *country == source_country)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::Utc;
+    use crate::adapters::logger_slog::SlogLoggerAdapter;
+    use chrono::{TimeZone, Utc};
 
     #[test]
     fn test_to_country() {
-        let country = to_country(&"France".to_string(), CountryCode::FRA);
+        let country = to_country("France", CountryCode::FRA);
         assert_eq!(country, Some(CountryCode::FRA));
-        let country = to_country(&"Fake".to_string(), CountryCode::FRA);
+        let country = to_country("Fake", CountryCode::FRA);
+        assert_eq!(country, None);
+        let country = to_country("Germany", CountryCode::DEU);
+        assert_eq!(country, Some(CountryCode::DEU));
+        let country = to_country("Germany", CountryCode::FRA);
         assert_eq!(country, None);
     }
 
+    #[test]
+    fn test_source_country_filter() {
+        assert_eq!(source_country_filter(CountryCode::FRA), "sourcecountry:FR");
+        assert_eq!(source_country_filter(CountryCode::DEU), "sourcecountry:GM");
+        assert_eq!(
+            source_country_filter(CountryCode::PSE),
+            "(sourcecountry:WE OR sourcecountry:GZ)"
+        );
+        assert_eq!(
+            source_country_filter(CountryCode::ALA),
+            "sourcecountry:ålandislands"
+        );
+    }
+
     #[test]
     fn test_to_datetime() {
-        let date = to_datetime(&"20230624T121500Z".to_string()).unwrap();
+        let date = to_datetime("20230624T121500Z").unwrap();
         let d = Utc.with_ymd_and_hms(2023, 6, 24, 12, 15, 0).unwrap();
         assert_eq!(date, d);
-        let invalid_date = to_datetime(&"invalid_date".to_string());
+        let invalid_date = to_datetime("invalid_date");
         assert!(invalid_date.is_err());
     }
 
@@ -262,10 +671,12 @@ mod tests {
         let start_time = Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap();
         let end_time = Utc.with_ymd_and_hms(2021, 1, 2, 0, 0, 0).unwrap();
         let url = build_url(
+            DEFAULT_BASE_URL,
+            ApiMode::ArtList,
             start_time,
             end_time,
             CountryCode::FRA,
-            "climate change".to_string(),
+            "\"climate change\"",
         );
         assert_eq!(
             url,
@@ -273,14 +684,85 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_to_gdelt_query() {
+        let to_query = |input: &str| {
+            to_gdelt_query(&CategoryExpression::parse(input).unwrap()).map_err(|e| e.to_string())
+        };
+        assert_eq!(
+            to_query("\"climate change\"").unwrap(),
+            "\"climate change\""
+        );
+        assert_eq!(
+            to_query("\"climate change\" OR \"global warming\" OR \"changement climatique\"")
+                .unwrap(),
+            "(\"climate change\" OR \"global warming\" OR \"changement climatique\")"
+        );
+        assert_eq!(
+            to_query("\"heat wave\" AND NOT sport").unwrap(),
+            "\"heat wave\" -\"sport\""
+        );
+        assert_eq!(
+            to_query("(drought OR \"heat wave\") europe").unwrap(),
+            "(\"drought\" OR \"heat wave\") \"europe\""
+        );
+        assert!(to_query("drought AND NOT (sport OR music)").is_err());
+        assert!(to_query("(drought AND europe) OR flood").is_err());
+    }
+
+    #[test]
+    fn test_parse_articles() {
+        let body = br#"{"articles": [{"url": "https://example.com", "title": "Title", "seendate": "20230624T121500Z", "domain": "example.com", "language": "French", "sourcecountry": "France"}]}"#;
+        assert_eq!(parse_articles(body).unwrap().len(), 1);
+        assert!(parse_articles(b"{}").unwrap().is_empty());
+
+        let throttled = parse_articles(b"Please limit requests to one every 5 seconds");
+        assert!(matches!(throttled, Err(SearchClientError::Throttled(_))));
+        let invalid = parse_articles(b"Invalid query");
+        assert!(matches!(invalid, Err(SearchClientError::Parse(_))));
+    }
+
+    #[test]
+    fn test_parse_timeline() {
+        let body = br#"{"query_details": {"title": "", "date_resolution": "day"}, "timeline": [{"series": "Article Count", "data": [{"date": "20230617T000000Z", "value": 12, "norm": 4530}, {"date": "20230618T000000Z", "value": 0, "norm": 4012}]}]}"#;
+        let points = to_coverage_points(parse_timeline(body).unwrap(), &SlogLoggerAdapter::new());
+        assert_eq!(points.len(), 2);
+        assert_eq!(
+            points[0].datetime,
+            Utc.with_ymd_and_hms(2023, 6, 17, 0, 0, 0).unwrap()
+        );
+        assert_eq!(points[0].value, 12.0);
+        assert_eq!(points[0].total, Some(4530));
+        assert!(parse_timeline(b"{}").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_split_window() {
+        let start_time = Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap();
+        let end_time = Utc.with_ymd_and_hms(2021, 1, 2, 0, 0, 0).unwrap();
+        let window = DateRange::new(start_time, end_time).unwrap();
+        let (earlier, later) = split_window(&window).unwrap();
+        let midpoint = Utc.with_ymd_and_hms(2021, 1, 1, 12, 0, 0).unwrap();
+        assert_eq!(earlier.inclusive_start_date, start_time);
+        assert_eq!(earlier.inclusive_end_date, midpoint);
+        assert_eq!(later.inclusive_start_date, midpoint);
+        assert_eq!(later.inclusive_end_date, end_time);
+
+        let one_second =
+            DateRange::new(start_time, start_time + chrono::Duration::seconds(1)).unwrap();
+        assert!(split_window(&one_second).is_none());
+    }
+
     #[test]
     fn test_to_news_article() {
         let mut articles = Vec::new();
 
         let valid_article = GDeltaArticle {
             url: "https://example.com".to_string(),
+            url_mobile: "https://m.example.com".to_string(),
             title: "Valid Article".to_string(),
             seendate: "20230624T121500Z".to_string(),
+            socialimage: "".to_string(),
             domain: "example.com".to_string(),
             language: "French".to_string(),
             sourcecountry: "France".to_string(),
@@ -289,8 +771,10 @@ mod tests {
 
         let invalid_date_article = GDeltaArticle {
             url: "https://invalid.com".to_string(),
+            url_mobile: "".to_string(),
             title: "Invalid Date Article".to_string(),
             seendate: "invalid_date".to_string(),
+            socialimage: "".to_string(),
             domain: "invalid.com".to_string(),
             language: "French".to_string(),
             sourcecountry: "France".to_string(),
@@ -299,18 +783,29 @@ mod tests {
 
         let invalid_country_article = GDeltaArticle {
             url: "https://invalid.com".to_string(),
+            url_mobile: "".to_string(),
             title: "Invalid Country Article".to_string(),
             seendate: "20230624T121500Z".to_string(),
+            socialimage: "".to_string(),
             domain: "invalid.com".to_string(),
             language: "French".to_string(),
             sourcecountry: "INVALID".to_string(),
         };
         articles.push(invalid_country_article);
 
-        let news_articles =
-            to_news_article(articles, &"climate change".to_string(), CountryCode::FRA);
+        let news_articles = to_news_article(
+            articles,
+            "climate change",
+            CountryCode::FRA,
+            &SlogLoggerAdapter::new(),
+        );
 
         assert_eq!(news_articles.len(), 1);
         assert_eq!(news_articles[0].title, "Valid Article");
+        assert_eq!(
+            news_articles[0].url_mobile,
+            Some("https://m.example.com".to_string())
+        );
+        assert_eq!(news_articles[0].social_image, None);
     }
 }
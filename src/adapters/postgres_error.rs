@@ -0,0 +1,60 @@
+use crate::core::ports::RepositoryError;
+
+const UNIQUE_VIOLATION: &str = "23505";
+const FOREIGN_KEY_VIOLATION: &str = "23503";
+
+// Lets the Postgres adapters use `?` on sqlx results
+impl From<sqlx::Error> for RepositoryError {
+    fn from(err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound("Row".to_string()),
+            sqlx::Error::Database(db_err) => match db_err.code().as_deref() {
+                Some(UNIQUE_VIOLATION) => RepositoryError::Conflict(db_err.to_string()),
+                // A row referencing one that doesn't exist. Deletes of rows that are still
+                // referenced fail the same way, the adapters doing those check for it themselves.
+                Some(FOREIGN_KEY_VIOLATION) => {
+                    RepositoryError::NotFound("Referenced row".to_string())
+                }
+                _ => RepositoryError::Other(db_err.to_string()),
+            },
+            sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_)
+            | sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::WorkerCrashed => RepositoryError::Unavailable(err.to_string()),
+            sqlx::Error::ColumnDecode { .. } | sqlx::Error::Decode(_) => {
+                RepositoryError::InvalidData(err.to_string())
+            }
+            _ => RepositoryError::Other(err.to_string()),
+        }
+    }
+}
+
+pub fn is_foreign_key_violation(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Database(db_err) => db_err.code().as_deref() == Some(FOREIGN_KEY_VIOLATION),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repository_error_from_sqlx_error() {
+        assert_eq!(
+            RepositoryError::from(sqlx::Error::PoolTimedOut),
+            RepositoryError::Unavailable(sqlx::Error::PoolTimedOut.to_string())
+        );
+        assert!(matches!(
+            RepositoryError::from(sqlx::Error::RowNotFound),
+            RepositoryError::NotFound(_)
+        ));
+        assert!(matches!(
+            RepositoryError::from(sqlx::Error::Protocol("unexpected message".to_string())),
+            RepositoryError::Other(_)
+        ));
+        assert!(!is_foreign_key_violation(&sqlx::Error::RowNotFound));
+    }
+}
@@ -0,0 +1,175 @@
+use crate::adapters::news_repository_postgres::get_country_code;
+use crate::core::domain::{ArticleQuery, DateRange, QueuedQuery};
+use crate::core::ports::RepositoryError;
+use crate::core::ports::{self, SyncJobQueue};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use std::time::Duration;
+
+// Job queue stored in the sync_jobs table, workers claim rows with FOR UPDATE SKIP LOCKED so
+// they never block on or pick up the same job
+pub struct PostgresSyncJobQueue {
+    pool: PgPool,
+    logger: Box<dyn ports::Logger>,
+}
+
+impl PostgresSyncJobQueue {
+    pub fn new(pool: PgPool, logger: Box<dyn ports::Logger>) -> Self {
+        Self { pool, logger }
+    }
+}
+
+#[async_trait]
+impl SyncJobQueue for PostgresSyncJobQueue {
+    async fn enqueue(
+        &self,
+        queries: Vec<ArticleQuery>,
+        max_attempts: i32,
+    ) -> Result<i32, RepositoryError> {
+        let mut tx = self.pool.begin().await?;
+        let mut num_queued = 0;
+        for query in &queries {
+            sqlx::query(
+                "INSERT INTO sync_jobs (country_iso_alpha_3, category_name, inclusive_start_date,
+                    inclusive_end_date, max_attempts)
+                VALUES ($1, $2, $3, $4, $5)",
+            )
+            .bind(query.source_country.alpha3())
+            .bind(&query.category)
+            .bind(query.date_range.inclusive_start_date)
+            .bind(query.date_range.inclusive_end_date)
+            .bind(max_attempts)
+            .execute(&mut tx)
+            .await?;
+            num_queued += 1;
+        }
+        tx.commit().await?;
+
+        Ok(num_queued)
+    }
+
+    async fn claim(
+        &self,
+        worker_id: &str,
+        lease: Duration,
+    ) -> Result<Option<QueuedQuery>, RepositoryError> {
+        // Jobs whose worker stopped on their last attempt won't be claimed again
+        let abandoned = sqlx::query(
+            "UPDATE sync_jobs
+            SET status = 'failed', finished_at = now(),
+                last_error = COALESCE(last_error, 'Worker stopped before finishing')
+            WHERE status = 'running' AND lease_expires_at < now() AND attempts >= max_attempts",
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        if abandoned > 0 {
+            self.logger.warn(&format!(
+                "Gave up on {} sync jobs abandoned by their workers",
+                abandoned
+            ));
+        }
+
+        let row = sqlx::query(
+            "UPDATE sync_jobs
+            SET status = 'running', attempts = attempts + 1, worker_id = $1,
+                lease_expires_at = now() + make_interval(secs => $2), heartbeat_at = now()
+            WHERE id = (
+                SELECT id FROM sync_jobs
+                WHERE (status = 'queued' OR (status = 'running' AND lease_expires_at < now()))
+                AND attempts < max_attempts
+                ORDER BY id
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, country_iso_alpha_3, category_name, inclusive_start_date,
+                inclusive_end_date, attempts, max_attempts",
+        )
+        .bind(worker_id)
+        .bind(lease.as_secs_f64())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) => Ok(Some(to_queued_query(row)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn heartbeat(
+        &self,
+        id: i64,
+        worker_id: &str,
+        lease: Duration,
+    ) -> Result<bool, RepositoryError> {
+        let updated = sqlx::query(
+            "UPDATE sync_jobs
+            SET lease_expires_at = now() + make_interval(secs => $3), heartbeat_at = now()
+            WHERE id = $1 AND worker_id = $2 AND status = 'running'",
+        )
+        .bind(id)
+        .bind(worker_id)
+        .bind(lease.as_secs_f64())
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        Ok(updated > 0)
+    }
+
+    async fn complete(
+        &self,
+        id: i64,
+        worker_id: &str,
+        inserted: i32,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "UPDATE sync_jobs
+            SET status = 'completed', inserted = $3, lease_expires_at = NULL, finished_at = now()
+            WHERE id = $1 AND worker_id = $2",
+        )
+        .bind(id)
+        .bind(worker_id)
+        .bind(inserted)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn fail(&self, id: i64, worker_id: &str, error: &str) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "UPDATE sync_jobs
+            SET status = CASE WHEN attempts < max_attempts THEN 'queued' ELSE 'failed' END,
+                finished_at = CASE WHEN attempts < max_attempts THEN NULL ELSE now() END,
+                last_error = $3, lease_expires_at = NULL
+            WHERE id = $1 AND worker_id = $2",
+        )
+        .bind(id)
+        .bind(worker_id)
+        .bind(error)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+fn to_queued_query(row: PgRow) -> Result<QueuedQuery, RepositoryError> {
+    let source_country = get_country_code(&row, "country_iso_alpha_3")?;
+    let start: DateTime<Utc> = row.get("inclusive_start_date");
+    let end: DateTime<Utc> = row.get("inclusive_end_date");
+
+    Ok(QueuedQuery {
+        id: row.get("id"),
+        query: ArticleQuery::new(
+            source_country,
+            row.get("category_name"),
+            DateRange::new(start, end).map_err(|e| RepositoryError::InvalidData(e.to_string()))?,
+        ),
+        attempts: row.get("attempts"),
+        max_attempts: row.get("max_attempts"),
+    })
+}
@@ -0,0 +1,62 @@
+use crate::core::ports::RepositoryError;
+use crate::core::ports::SyncLock;
+use async_trait::async_trait;
+use sqlx::pool::PoolConnection;
+use sqlx::{Connection, PgPool, Postgres};
+use tokio::sync::Mutex;
+
+// Key of the advisory lock taken by the sync scheduler
+pub const SYNC_LOCK_KEY: i64 = 0x6764_656c_7473_796e;
+
+// Session level Postgres advisory lock, the connection holding it is kept out of the pool until
+// the lock is released. Postgres releases the lock itself when the process dies.
+pub struct PostgresSyncLock {
+    pool: PgPool,
+    key: i64,
+    connection: Mutex<Option<PoolConnection<Postgres>>>,
+}
+
+impl PostgresSyncLock {
+    pub fn new(pool: PgPool, key: i64) -> Self {
+        Self {
+            pool,
+            key,
+            connection: Mutex::new(None),
+        }
+    }
+}
+
+#[async_trait]
+impl SyncLock for PostgresSyncLock {
+    async fn try_lock(&self) -> Result<bool, RepositoryError> {
+        let mut held = self.connection.lock().await;
+        if held.is_some() {
+            return Ok(true);
+        }
+        let mut connection = self.pool.acquire().await?;
+        let (locked,): (bool,) = sqlx::query_as("SELECT pg_try_advisory_lock($1)")
+            .bind(self.key)
+            .fetch_one(&mut connection)
+            .await?;
+        if locked {
+            *held = Some(connection);
+        }
+        Ok(locked)
+    }
+
+    async fn unlock(&self) -> Result<(), RepositoryError> {
+        if let Some(mut connection) = self.connection.lock().await.take() {
+            let unlocked = sqlx::query("SELECT pg_advisory_unlock($1)")
+                .bind(self.key)
+                .execute(&mut connection)
+                .await;
+            if let Err(e) = unlocked {
+                // Back in the pool the connection would keep the lock, closing it ends the
+                // session and with it the lock
+                let _ = connection.detach().close().await;
+                return Err(e.into());
+            }
+        }
+        Ok(())
+    }
+}
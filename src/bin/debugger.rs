@@ -1,7 +1,9 @@
 use learn_rust::adapters;
+use learn_rust::adapters::gdeltproject_scheduler::RequestSchedulerConfig;
 use learn_rust::adapters::logger_slog::SlogLoggerAdapter;
 use learn_rust::adapters::news_search_client_gdeltproject;
 use learn_rust::core;
+use learn_rust::core::domain::SyncMode;
 use learn_rust::core::ports::Logger;
 use learn_rust::core::ports::NewsService;
 use learn_rust::infrastructure;
@@ -9,7 +11,6 @@ use learn_rust::infrastructure;
 extern crate chrono;
 extern crate serde_derive;
 extern crate serde_json;
-extern crate ureq;
 use std::env;
 
 use dotenv::dotenv;
@@ -26,6 +27,12 @@ async fn main() {
     let db_name = env::var("POSTGRES_DB").unwrap_or_else(|_| String::from("postgres"));
     let db_host = env::var("DB_HOST").unwrap_or_else(|_| String::from("localhost"));
     let db_port = env::var("DB_PORT").unwrap_or_else(|_| String::from("15432"));
+    let gdelt_base_url = env::var("GDELT_BASE_URL")
+        .unwrap_or_else(|_| String::from(news_search_client_gdeltproject::DEFAULT_BASE_URL));
+    let sync_concurrency = env::var("SYNC_CONCURRENCY")
+        .ok()
+        .and_then(|concurrency| concurrency.parse().ok())
+        .unwrap_or(core::service::DEFAULT_SYNC_CONCURRENCY);
     logger.info(
         format!(
             "Attempting to connect to Postgres, host: {}, port: {}",
@@ -42,12 +49,19 @@ async fn main() {
         adapters::news_repository_postgres::PostgresNewsRepository::new(pool, logger.clone());
 
     let g_delta_project_adapter =
-        news_search_client_gdeltproject::GDeltaProjectNewsSearchAdapter::new(logger.clone());
-    let news_service = Arc::new(core::service::NewsService::new(
-        logger.clone(),
-        Box::new(repo),
-        Arc::new(g_delta_project_adapter),
-    ));
+        news_search_client_gdeltproject::GDeltaProjectNewsSearchAdapter::new(
+            logger.clone(),
+            gdelt_base_url,
+            RequestSchedulerConfig::default(),
+        );
+    let news_service = Arc::new(
+        core::service::NewsService::new(
+            logger.clone(),
+            Box::new(repo),
+            Arc::new(g_delta_project_adapter),
+        )
+        .with_sync_concurrency(sync_concurrency),
+    );
 
     let date_range = match core::domain::DateRange::new(
         chrono::Utc::now() - chrono::Duration::days(60),
@@ -59,12 +73,41 @@ async fn main() {
         Err(e) => panic!("{}", e),
     };
 
+    // "full" re-fetches the whole date range, otherwise each country and category continues
+    // from where its last successful sync ended
+    let sync_mode = match env::var("SYNC_MODE") {
+        Ok(mode) => match SyncMode::from_name(&mode) {
+            Some(sync_mode) => sync_mode,
+            None => panic!(
+                "Invalid SYNC_MODE {:?}, expected \"full\" or \"incremental\"",
+                mode
+            ),
+        },
+        Err(_) => SyncMode::Incremental,
+    };
+    let synced = match sync_mode {
+        SyncMode::Full => news_service.sync_articles(date_range.clone()).await,
+        SyncMode::Incremental => {
+            news_service
+                .sync_articles_incrementally(date_range.inclusive_start_date)
+                .await
+        }
+    };
+    match synced.map_err(|e| e.to_string()) {
+        Ok(report) => logger.info(&format!(
+            "Synced articles, {} of {} queries failed",
+            report.failed_queries().count(),
+            report.queries.len()
+        )),
+        Err(e) => panic!("{}", e),
+    };
+
     match news_service
-        .sync_articles(date_range)
+        .sync_tone_timelines(date_range)
         .await
         .map_err(|e| e.to_string())
     {
-        Ok(_) => logger.info("Successfully synced articles"),
+        Ok(_) => logger.info("Successfully synced tone timelines"),
         Err(e) => panic!("{}", e),
     };
 }
@@ -9,12 +9,13 @@ struct Person {
 }
 
 async fn some_computation(name: &str) -> Person {
-    let resp = ureq::get(format!("https://api.agify.io/?name={}", name).as_str())
-        .call()
-        .unwrap()
-        .into_string()
-        .unwrap();
-    let parsed: serde_json::Value = serde_json::from_str(resp.as_str()).unwrap();
+    let parsed: serde_json::Value =
+        reqwest::get(format!("https://api.agify.io/?name={}", name).as_str())
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
     let age = parsed["age"].as_u64().unwrap();
     Person {
         name: name.to_string(),
@@ -0,0 +1,69 @@
+use learn_rust::adapters;
+use learn_rust::adapters::gdeltproject_scheduler::RequestSchedulerConfig;
+use learn_rust::adapters::logger_slog::SlogLoggerAdapter;
+use learn_rust::adapters::news_archive_gdelt_files::GDeltaFilesNewsArchive;
+use learn_rust::adapters::news_search_client_gdeltproject;
+use learn_rust::core;
+use learn_rust::core::ports::Logger;
+use learn_rust::core::ports::NewsService;
+use learn_rust::infrastructure;
+
+use std::env;
+use std::path::PathBuf;
+
+use dotenv::dotenv;
+use std::sync::Arc;
+
+// Backfills articles from GDELT 2.0 export files, the directory holding them is the first
+// argument or GDELT_EXPORT_DIR
+#[tokio::main]
+async fn main() {
+    let logger = Box::new(SlogLoggerAdapter::new());
+    dotenv().ok();
+
+    let export_dir = match env::args()
+        .nth(1)
+        .or_else(|| env::var("GDELT_EXPORT_DIR").ok())
+    {
+        Some(export_dir) => PathBuf::from(export_dir),
+        None => panic!("Usage: gdelt_import <directory with GDELT export files>"),
+    };
+
+    let db_user = env::var("POSTGRES_USER").unwrap_or_else(|_| String::from("postgres"));
+    let db_password = env::var("POSTGRES_PASSWORD").unwrap_or_else(|_| String::from("postgres"));
+    let db_name = env::var("POSTGRES_DB").unwrap_or_else(|_| String::from("postgres"));
+    let db_host = env::var("DB_HOST").unwrap_or_else(|_| String::from("localhost"));
+    let db_port = env::var("DB_PORT").unwrap_or_else(|_| String::from("15432"));
+    let gdelt_base_url = env::var("GDELT_BASE_URL")
+        .unwrap_or_else(|_| String::from(news_search_client_gdeltproject::DEFAULT_BASE_URL));
+
+    let pool =
+        infrastructure::postgres::get_db_pool(db_user, db_password, db_name, db_host, db_port)
+            .await
+            .expect("Failed to connect to Postgres");
+    logger.info("Successfully connected to Postgres");
+
+    let repo =
+        adapters::news_repository_postgres::PostgresNewsRepository::new(pool, logger.clone());
+    let g_delta_project_adapter =
+        news_search_client_gdeltproject::GDeltaProjectNewsSearchAdapter::new(
+            logger.clone(),
+            gdelt_base_url,
+            RequestSchedulerConfig::default(),
+        );
+    let news_service = core::service::NewsService::new(
+        logger.clone(),
+        Box::new(repo),
+        Arc::new(g_delta_project_adapter),
+    );
+
+    let archive = GDeltaFilesNewsArchive::new(logger.clone(), export_dir);
+    match news_service
+        .import_articles(Arc::new(archive))
+        .await
+        .map_err(|e| e.to_string())
+    {
+        Ok(count) => logger.info(&format!("Successfully imported {} articles", count)),
+        Err(e) => panic!("{}", e),
+    };
+}
@@ -6,9 +6,9 @@ use learn_rust::infrastructure;
 extern crate chrono;
 extern crate serde_derive;
 extern crate serde_json;
-extern crate ureq;
 use std::env;
 
+use crate::adapters::gdeltproject_scheduler::RequestSchedulerConfig;
 use crate::adapters::logger_slog::SlogLoggerAdapter;
 use crate::adapters::news_search_client_gdeltproject;
 
@@ -24,6 +24,8 @@ async fn main() {
     let db_name = env::var("POSTGRES_DB").unwrap_or_else(|_| String::from("postgres"));
     let db_host = env::var("DB_HOST").unwrap_or_else(|_| String::from("localhost"));
     let db_port = env::var("DB_PORT").unwrap_or_else(|_| String::from("15432"));
+    let gdelt_base_url = env::var("GDELT_BASE_URL")
+        .unwrap_or_else(|_| String::from(news_search_client_gdeltproject::DEFAULT_BASE_URL));
 
     let pool =
         infrastructure::postgres::get_db_pool(db_user, db_password, db_name, db_host, db_port)
@@ -35,7 +37,11 @@ async fn main() {
         adapters::news_repository_postgres::PostgresNewsRepository::new(pool, logger.clone());
 
     let g_delta_project_adapter =
-        news_search_client_gdeltproject::GDeltaProjectNewsSearchAdapter::new(logger.clone());
+        news_search_client_gdeltproject::GDeltaProjectNewsSearchAdapter::new(
+            logger.clone(),
+            gdelt_base_url,
+            RequestSchedulerConfig::default(),
+        );
     let news_service = Arc::new(core::service::NewsService::new(
         logger.clone(),
         Box::new(repo),
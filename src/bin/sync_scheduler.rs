@@ -0,0 +1,89 @@
+use learn_rust::adapters;
+use learn_rust::adapters::gdeltproject_scheduler::RequestSchedulerConfig;
+use learn_rust::adapters::logger_slog::SlogLoggerAdapter;
+use learn_rust::adapters::news_search_client_gdeltproject;
+use learn_rust::adapters::sync_lock_postgres::{PostgresSyncLock, SYNC_LOCK_KEY};
+use learn_rust::core;
+use learn_rust::core::ports::Logger;
+use learn_rust::core::scheduler::{SyncSchedule, SyncScheduler};
+use learn_rust::infrastructure;
+
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::signal::unix::{signal, SignalKind};
+
+// Keeps running incremental syncs on SYNC_SCHEDULE, an interval such as "15m" or a cron
+// expression with a seconds field such as "0 */15 * * * *". On SIGTERM or ctrl-c the queries
+// in flight are finished before exiting.
+#[tokio::main]
+async fn main() {
+    let logger = Box::new(SlogLoggerAdapter::new());
+    dotenv().ok();
+
+    let schedule = env::var("SYNC_SCHEDULE").unwrap_or_else(|_| String::from("15m"));
+    let schedule = match SyncSchedule::parse(&schedule) {
+        Ok(schedule) => schedule,
+        Err(e) => panic!("{}", e),
+    };
+    let db_user = env::var("POSTGRES_USER").unwrap_or_else(|_| String::from("postgres"));
+    let db_password = env::var("POSTGRES_PASSWORD").unwrap_or_else(|_| String::from("postgres"));
+    let db_name = env::var("POSTGRES_DB").unwrap_or_else(|_| String::from("postgres"));
+    let db_host = env::var("DB_HOST").unwrap_or_else(|_| String::from("localhost"));
+    let db_port = env::var("DB_PORT").unwrap_or_else(|_| String::from("15432"));
+    let gdelt_base_url = env::var("GDELT_BASE_URL")
+        .unwrap_or_else(|_| String::from(news_search_client_gdeltproject::DEFAULT_BASE_URL));
+    let sync_concurrency = env::var("SYNC_CONCURRENCY")
+        .ok()
+        .and_then(|concurrency| concurrency.parse().ok())
+        .unwrap_or(core::service::DEFAULT_SYNC_CONCURRENCY);
+
+    let pool =
+        infrastructure::postgres::get_db_pool(db_user, db_password, db_name, db_host, db_port)
+            .await
+            .expect("Failed to connect to Postgres");
+    logger.info("Successfully connected to Postgres");
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let lock = Arc::new(PostgresSyncLock::new(pool.clone(), SYNC_LOCK_KEY));
+    let repo =
+        adapters::news_repository_postgres::PostgresNewsRepository::new(pool, logger.clone());
+    let g_delta_project_adapter =
+        news_search_client_gdeltproject::GDeltaProjectNewsSearchAdapter::new(
+            logger.clone(),
+            gdelt_base_url,
+            RequestSchedulerConfig::default(),
+        );
+    let news_service = Arc::new(
+        core::service::NewsService::new(
+            logger.clone(),
+            Box::new(repo),
+            Arc::new(g_delta_project_adapter),
+        )
+        .with_sync_concurrency(sync_concurrency)
+        .with_stop_signal(stop.clone()),
+    );
+
+    let stop_on_signal = stop.clone();
+    let signal_logger = logger.clone();
+    tokio::spawn(async move {
+        let mut terminate = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+        tokio::select! {
+            _ = terminate.recv() => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+        signal_logger.info("Stopping after the queries in flight");
+        stop_on_signal.store(true, Ordering::SeqCst);
+    });
+
+    let scheduler = SyncScheduler::new(
+        logger.clone(),
+        news_service,
+        lock,
+        schedule,
+        chrono::Duration::days(60),
+    );
+    scheduler.run(stop).await;
+}
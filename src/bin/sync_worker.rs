@@ -0,0 +1,116 @@
+use learn_rust::adapters;
+use learn_rust::adapters::gdeltproject_scheduler::RequestSchedulerConfig;
+use learn_rust::adapters::logger_slog::SlogLoggerAdapter;
+use learn_rust::adapters::news_search_client_gdeltproject;
+use learn_rust::adapters::sync_job_queue_postgres::PostgresSyncJobQueue;
+use learn_rust::core;
+use learn_rust::core::ports::{Logger, NewsRepository};
+use learn_rust::core::sync_worker::{SyncWorker, SyncWorkerConfig};
+use learn_rust::infrastructure;
+
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeZone, Utc};
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use dotenv::dotenv;
+use std::sync::Arc;
+
+// Works through the sync_jobs queue, start as many as needed. To fill the queue with a backfill
+// split into windows of WINDOW_DAYS (7 by default):
+//   sync_worker enqueue <inclusive start YYYY-MM-DD> <inclusive end YYYY-MM-DD>
+#[tokio::main]
+async fn main() {
+    let logger = Box::new(SlogLoggerAdapter::new());
+    dotenv().ok();
+
+    let db_user = env::var("POSTGRES_USER").unwrap_or_else(|_| String::from("postgres"));
+    let db_password = env::var("POSTGRES_PASSWORD").unwrap_or_else(|_| String::from("postgres"));
+    let db_name = env::var("POSTGRES_DB").unwrap_or_else(|_| String::from("postgres"));
+    let db_host = env::var("DB_HOST").unwrap_or_else(|_| String::from("localhost"));
+    let db_port = env::var("DB_PORT").unwrap_or_else(|_| String::from("15432"));
+    let gdelt_base_url = env::var("GDELT_BASE_URL")
+        .unwrap_or_else(|_| String::from(news_search_client_gdeltproject::DEFAULT_BASE_URL));
+
+    let pool =
+        infrastructure::postgres::get_db_pool(db_user, db_password, db_name, db_host, db_port)
+            .await
+            .expect("Failed to connect to Postgres");
+    logger.info("Successfully connected to Postgres");
+
+    let queue = Arc::new(PostgresSyncJobQueue::new(pool.clone(), logger.clone()));
+    let repo =
+        adapters::news_repository_postgres::PostgresNewsRepository::new(pool, logger.clone());
+
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) == Some("enqueue") {
+        let (start, end) = match (args.get(2), args.get(3)) {
+            (Some(start), Some(end)) => (parse_date(start), parse_date(end)),
+            _ => panic!("Usage: sync_worker enqueue <inclusive start> <inclusive end>"),
+        };
+        let date_range = core::domain::DateRange::new(start, end).expect("Invalid date range");
+        let window_days = env::var("WINDOW_DAYS")
+            .ok()
+            .and_then(|days| days.parse().ok())
+            .unwrap_or(7);
+        let categories = repo
+            .get_categories()
+            .await
+            .expect("Failed to get categories");
+        let countries = repo.get_countries().await.expect("Failed to get countries");
+        match core::sync_worker::enqueue_backfill(
+            queue.as_ref(),
+            categories,
+            countries,
+            date_range,
+            chrono::Duration::days(window_days),
+            core::sync_worker::DEFAULT_MAX_ATTEMPTS,
+        )
+        .await
+        .map_err(|e| e.to_string())
+        {
+            Ok(count) => logger.info(&format!("Queued {} sync jobs", count)),
+            Err(e) => panic!("{}", e),
+        };
+        return;
+    }
+
+    let g_delta_project_adapter =
+        news_search_client_gdeltproject::GDeltaProjectNewsSearchAdapter::new(
+            logger.clone(),
+            gdelt_base_url,
+            RequestSchedulerConfig::default(),
+        );
+    let news_service = Arc::new(core::service::NewsService::new(
+        logger.clone(),
+        Box::new(repo),
+        Arc::new(g_delta_project_adapter),
+    ));
+
+    let worker_id = env::var("WORKER_ID").unwrap_or_else(|_| {
+        let host = env::var("HOSTNAME").unwrap_or_else(|_| String::from("worker"));
+        format!("{}-{}", host, std::process::id())
+    });
+    let worker = SyncWorker::new(
+        logger.clone(),
+        news_service,
+        queue,
+        worker_id,
+        SyncWorkerConfig::default(),
+    );
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_on_signal = stop.clone();
+    let signal_logger = logger.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            signal_logger.info("Stopping after the current sync job");
+            stop_on_signal.store(true, Ordering::SeqCst);
+        }
+    });
+    worker.run(stop).await;
+}
+
+fn parse_date(date: &str) -> DateTime<Utc> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").expect("Dates must be YYYY-MM-DD");
+    Utc.from_utc_datetime(&date.and_time(NaiveTime::from_hms_opt(0, 0, 0).unwrap()))
+}
@@ -1,18 +1,65 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
 use isocountry::CountryCode;
 use serde::Serializer;
+use std::fmt;
 use thiserror::Error;
 
-#[derive(Debug, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize)]
 pub struct NewsArticle {
+    // Set once the article has been stored
+    pub id: Option<i32>,
     pub title: String,
-    pub category: String,
+    // Every category the article was found under or tagged with
+    pub categories: Vec<String>,
     #[serde(serialize_with = "serialize")]
     pub datetime: DateTime<Utc>,
     pub url: String,
     pub domain: String,
     pub language: String,
     pub country: CountryCode,
+    // Url of the mobile version of the article, when the source has one
+    pub url_mobile: Option<String>,
+    // Image shown when the article is shared on social media
+    pub social_image: Option<String>,
+}
+
+// A category articles are searched for and tagged with
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Category {
+    pub name: String,
+    // Boolean expression searched for instead of the name, see CategoryExpression
+    pub expression: Option<String>,
+}
+
+// A country articles are fetched from, with its codes spelled out for api clients
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Country {
+    pub alpha_2: &'static str,
+    pub alpha_3: &'static str,
+    pub name: &'static str,
+}
+
+impl From<CountryCode> for Country {
+    fn from(country: CountryCode) -> Self {
+        Self {
+            alpha_2: country.alpha2(),
+            alpha_3: country.alpha3(),
+            name: country.name(),
+        }
+    }
+}
+
+// Accepts an alpha 2 or alpha 3 code or the ISO name of the country, ignoring case
+pub fn parse_country_code(value: &str) -> Option<CountryCode> {
+    let value = value.trim();
+    CountryCode::for_alpha2_caseless(value)
+        .or_else(|_| CountryCode::for_alpha3_caseless(value))
+        .ok()
+        .or_else(|| {
+            CountryCode::iter()
+                .find(|country| country.name().eq_ignore_ascii_case(value))
+                .copied()
+        })
 }
 
 #[derive(Debug)]
@@ -20,12 +67,37 @@ pub struct ArticleQuery {
     pub source_country: CountryCode,
     pub category: String,
     pub date_range: DateRange,
+    // Extra phrases searched for next to the category name, results are still tagged with
+    // the category
+    pub keywords: Vec<CategoryKeyword>,
+    // Definition of the category when it is more than its name, see CategoryExpression
+    pub expression: Option<CategoryExpression>,
+}
+
+// A synonym or translation of a category, e.g. "global warming" or "changement climatique"
+// for "climate change"
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct CategoryKeyword {
+    pub category: String,
+    pub phrase: String,
+    // Language the phrase is written in, None when it isn't language specific
+    pub language: Option<String>,
+}
+
+impl CategoryKeyword {
+    pub fn new(category: String, phrase: String, language: Option<String>) -> Self {
+        Self {
+            category,
+            phrase,
+            language,
+        }
+    }
 }
 
 impl NewsArticle {
     pub fn new(
         title: String,
-        category: String,
+        categories: Vec<String>,
         datetime: DateTime<Utc>,
         url: String,
         domain: String,
@@ -33,13 +105,16 @@ impl NewsArticle {
         country: CountryCode,
     ) -> Self {
         Self {
+            id: None,
             title,
-            category,
+            categories,
             datetime,
             url,
             domain,
             language,
             country,
+            url_mobile: None,
+            social_image: None,
         }
     }
 }
@@ -50,8 +125,25 @@ impl ArticleQuery {
             source_country,
             category,
             date_range,
+            keywords: Vec::new(),
+            expression: None,
         }
     }
+
+    pub fn with_keywords(mut self, keywords: Vec<CategoryKeyword>) -> Self {
+        self.keywords = keywords;
+        self
+    }
+
+    pub fn with_expression(mut self, expression: Option<CategoryExpression>) -> Self {
+        self.expression = expression;
+        self
+    }
+
+    pub fn search_expression(&self) -> CategoryExpression {
+        CategoryExpression::for_category(&self.category, self.expression.as_ref(), &self.keywords)
+    }
+
     pub fn build_queries(
         categories: Vec<String>,
         countries: Vec<CountryCode>,
@@ -61,7 +153,7 @@ impl ArticleQuery {
         for country in countries {
             for category in &categories {
                 queries.push(ArticleQuery::new(
-                    country.clone(),
+                    country,
                     category.clone(),
                     date_range.clone(),
                 ));
@@ -71,9 +163,11 @@ impl ArticleQuery {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize)]
 pub struct DateRange {
+    #[serde(serialize_with = "serialize")]
     pub inclusive_start_date: DateTime<Utc>,
+    #[serde(serialize_with = "serialize")]
     pub inclusive_end_date: DateTime<Utc>,
 }
 
@@ -88,6 +182,730 @@ impl DateRange {
             Err(DateRangeError::InvalidDateRange)
         }
     }
+
+    // Consecutive ranges of at most `window` covering this one, the last one can be shorter.
+    // Like the rest of the date ranges both ends are inclusive, so neighbouring ranges share
+    // their boundary and an article seen exactly then is fetched twice, storing it again is a
+    // no-op.
+    pub fn split(&self, window: Duration) -> Vec<DateRange> {
+        let mut ranges = Vec::new();
+        let mut start = self.inclusive_start_date;
+        while start < self.inclusive_end_date {
+            let end = std::cmp::min(start + window, self.inclusive_end_date);
+            ranges.push(DateRange {
+                inclusive_start_date: start,
+                inclusive_end_date: end,
+            });
+            start = end;
+        }
+        ranges
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+// Where a page of articles ends, articles are ordered by when they were seen then by id
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArticleCursor {
+    pub seen_at: DateTime<Utc>,
+    pub id: i32,
+}
+
+impl ArticleCursor {
+    // An opaque token that can be put in a url as is
+    pub fn encode(&self) -> String {
+        format!(
+            "{}|{}",
+            self.seen_at.to_rfc3339_opts(SecondsFormat::Micros, true),
+            self.id
+        )
+        .bytes()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+    }
+
+    pub fn decode(token: &str) -> Result<Self, ArticleCursorError> {
+        let invalid = || ArticleCursorError::InvalidCursor(token.to_string());
+        if !token.len().is_multiple_of(2) || !token.is_ascii() {
+            return Err(invalid());
+        }
+        let bytes = (0..token.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&token[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| invalid())?;
+        let decoded = String::from_utf8(bytes).map_err(|_| invalid())?;
+        let (seen_at, id) = decoded.split_once('|').ok_or_else(invalid)?;
+        Ok(Self {
+            seen_at: DateTime::parse_from_rfc3339(seen_at)
+                .map_err(|_| invalid())?
+                .with_timezone(&Utc),
+            id: id.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+// A page of at most `limit` articles, starting after the cursor when there is one
+#[derive(Debug, Clone)]
+pub struct PageRequest {
+    pub limit: i64,
+    pub order: SortOrder,
+    pub after: Option<ArticleCursor>,
+}
+
+#[derive(Debug)]
+pub struct ArticlePage {
+    pub articles: Vec<NewsArticle>,
+    // None on the last page
+    pub next_cursor: Option<ArticleCursor>,
+}
+
+// Narrows down the articles of a query, empty lists don't filter anything. Languages and
+// domains are compared ignoring case.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArticleFilter {
+    pub countries: Vec<CountryCode>,
+    pub languages: Vec<String>,
+    pub domains: Vec<String>,
+    pub excluded_domains: Vec<String>,
+    // Case insensitive text the title has to contain
+    pub title_contains: Option<String>,
+}
+
+// Which measure of coverage a timeline holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoverageMode {
+    // Share of all monitored articles matching the query
+    Volume,
+    // Number of articles matching the query
+    VolumeRaw,
+}
+
+impl CoverageMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CoverageMode::Volume => "volume",
+            CoverageMode::VolumeRaw => "volume_raw",
+        }
+    }
+}
+
+// A single bucket of a coverage timeline, the search client picks the bucket size (e.g. 15
+// minutes or a day) based on the length of the date range
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CoveragePoint {
+    #[serde(serialize_with = "serialize")]
+    pub datetime: DateTime<Utc>,
+    pub value: f64,
+    // Total number of articles monitored in the bucket, when known
+    pub total: Option<i64>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct CoverageTimeline {
+    pub country: CountryCode,
+    pub category: String,
+    pub mode: CoverageMode,
+    pub points: Vec<CoveragePoint>,
+}
+
+// Average tone of the coverage in a bucket, negative values are negative coverage
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TonePoint {
+    #[serde(serialize_with = "serialize")]
+    pub datetime: DateTime<Utc>,
+    pub average_tone: f64,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct ToneTimeline {
+    pub country: CountryCode,
+    pub category: String,
+    pub points: Vec<TonePoint>,
+}
+
+// What happened to a batch of articles handed to the repository
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StoredArticles {
+    pub inserted: i32,
+    // Articles that were already stored
+    pub duplicates: i32,
+    pub failed: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncMode {
+    // Fetches the whole date range
+    Full,
+    // Fetches from each country and category's watermark
+    Incremental,
+}
+
+impl SyncMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SyncMode::Full => "full",
+            SyncMode::Incremental => "incremental",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "full" => Some(SyncMode::Full),
+            "incremental" => Some(SyncMode::Incremental),
+            _ => None,
+        }
+    }
+}
+
+// How syncing the articles of a single country and category went
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct QueryReport {
+    pub country: CountryCode,
+    pub category: String,
+    #[serde(serialize_with = "serialize")]
+    pub inclusive_start_date: DateTime<Utc>,
+    #[serde(serialize_with = "serialize")]
+    pub inclusive_end_date: DateTime<Utc>,
+    // Articles returned by the search client
+    pub fetched: i32,
+    pub inserted: i32,
+    pub duplicates: i32,
+    pub failed_windows: i32,
+    pub errors: Vec<String>,
+    pub duration_ms: i64,
+}
+
+impl QueryReport {
+    pub fn new(country: CountryCode, category: String, date_range: &DateRange) -> Self {
+        Self {
+            country,
+            category,
+            inclusive_start_date: date_range.inclusive_start_date,
+            inclusive_end_date: date_range.inclusive_end_date,
+            fetched: 0,
+            inserted: 0,
+            duplicates: 0,
+            failed_windows: 0,
+            errors: Vec::new(),
+            duration_ms: 0,
+        }
+    }
+
+    pub fn add_stored(&mut self, stored: StoredArticles) {
+        self.inserted += stored.inserted;
+        self.duplicates += stored.duplicates;
+        if stored.failed > 0 {
+            self.errors
+                .push(format!("Failed to store {} articles", stored.failed));
+        }
+    }
+
+    // Whether every article of the date range was fetched and stored
+    pub fn is_complete(&self) -> bool {
+        self.failed_windows == 0 && self.errors.is_empty()
+    }
+}
+
+// The outcome of a sync run, one report per country and category
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SyncReport {
+    // Set once the report is stored
+    pub id: Option<i32>,
+    pub mode: SyncMode,
+    #[serde(serialize_with = "serialize")]
+    pub started_at: DateTime<Utc>,
+    #[serde(serialize_with = "serialize")]
+    pub finished_at: DateTime<Utc>,
+    // The date range the run was asked to sync, incremental queries may start later
+    #[serde(serialize_with = "serialize")]
+    pub inclusive_start_date: DateTime<Utc>,
+    #[serde(serialize_with = "serialize")]
+    pub inclusive_end_date: DateTime<Utc>,
+    pub queries: Vec<QueryReport>,
+}
+
+impl SyncReport {
+    pub fn fetched(&self) -> i32 {
+        self.queries.iter().map(|query| query.fetched).sum()
+    }
+
+    pub fn inserted(&self) -> i32 {
+        self.queries.iter().map(|query| query.inserted).sum()
+    }
+
+    pub fn duplicates(&self) -> i32 {
+        self.queries.iter().map(|query| query.duplicates).sum()
+    }
+
+    pub fn failed_windows(&self) -> i32 {
+        self.queries.iter().map(|query| query.failed_windows).sum()
+    }
+
+    pub fn failed_queries(&self) -> impl Iterator<Item = &QueryReport> {
+        self.queries.iter().filter(|query| !query.is_complete())
+    }
+}
+
+// The totals of a sync run, without the report of every query
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SyncRunSummary {
+    pub id: i32,
+    pub mode: SyncMode,
+    #[serde(serialize_with = "serialize")]
+    pub started_at: DateTime<Utc>,
+    #[serde(serialize_with = "serialize")]
+    pub finished_at: DateTime<Utc>,
+    #[serde(serialize_with = "serialize")]
+    pub inclusive_start_date: DateTime<Utc>,
+    #[serde(serialize_with = "serialize")]
+    pub inclusive_end_date: DateTime<Utc>,
+    pub fetched: i32,
+    pub inserted: i32,
+    pub duplicates: i32,
+    pub failed_windows: i32,
+    pub failed_queries: i32,
+}
+
+impl SyncRunSummary {
+    pub fn new(id: i32, report: &SyncReport) -> Self {
+        Self {
+            id,
+            mode: report.mode,
+            started_at: report.started_at,
+            finished_at: report.finished_at,
+            inclusive_start_date: report.inclusive_start_date,
+            inclusive_end_date: report.inclusive_end_date,
+            fetched: report.fetched(),
+            inserted: report.inserted(),
+            duplicates: report.duplicates(),
+            failed_windows: report.failed_windows(),
+            failed_queries: report.failed_queries().count() as i32,
+        }
+    }
+}
+
+// When the articles of a country and category were last synced without failures
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SyncStatus {
+    pub country: CountryCode,
+    pub category: String,
+    // End of the last sync run in which the pair's query was complete
+    #[serde(serialize_with = "serialize_option")]
+    pub last_success_at: Option<DateTime<Utc>>,
+    // The pair's watermark, see SyncWatermark
+    #[serde(serialize_with = "serialize_option")]
+    pub synced_until: Option<DateTime<Utc>>,
+    // Seconds since the last success, None when the pair was never synced successfully
+    pub staleness_seconds: Option<i64>,
+}
+
+impl SyncStatus {
+    pub fn new(country: CountryCode, category: String) -> Self {
+        Self {
+            country,
+            category,
+            last_success_at: None,
+            synced_until: None,
+            staleness_seconds: None,
+        }
+    }
+}
+
+// The countries and categories a sync job fetches the articles of
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SyncRequest {
+    pub countries: Vec<CountryCode>,
+    pub categories: Vec<String>,
+    pub date_range: DateRange,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    // Finished, possibly with errors for some of the queries
+    Completed,
+    // Every query failed
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled
+        )
+    }
+}
+
+// A sync running in the background, one query per country and category
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SyncJob {
+    pub id: i64,
+    pub status: JobStatus,
+    pub request: SyncRequest,
+    pub total_queries: i32,
+    pub completed_queries: i32,
+    pub failed_queries: i32,
+    pub inserted: i32,
+    pub errors: Vec<String>,
+    #[serde(serialize_with = "serialize")]
+    pub created_at: DateTime<Utc>,
+    #[serde(serialize_with = "serialize_option")]
+    pub started_at: Option<DateTime<Utc>>,
+    #[serde(serialize_with = "serialize_option")]
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl SyncJob {
+    pub fn new(id: i64, request: SyncRequest) -> Self {
+        Self {
+            id,
+            status: JobStatus::Queued,
+            total_queries: (request.countries.len() * request.categories.len()) as i32,
+            request,
+            completed_queries: 0,
+            failed_queries: 0,
+            inserted: 0,
+            errors: Vec::new(),
+            created_at: Utc::now(),
+            started_at: None,
+            finished_at: None,
+        }
+    }
+}
+
+// A query waiting in, or claimed from, the job queue shared by the sync workers
+#[derive(Debug)]
+pub struct QueuedQuery {
+    pub id: i64,
+    pub query: ArticleQuery,
+    // Times the query has been claimed, including the current one
+    pub attempts: i32,
+    pub max_attempts: i32,
+}
+
+// How far the articles of a country and category have been synced
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncWatermark {
+    pub country: CountryCode,
+    pub category: String,
+    pub synced_until: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub enum WindowStatus {
+    Complete,
+    // The window still held more articles than the search client can return at once
+    // after being split down to the smallest possible size
+    Saturated,
+    Failed { attempts: u32, error: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct WindowOutcome {
+    pub window: DateRange,
+    pub status: WindowStatus,
+}
+
+// How each window of an ArticleQuery's date range was handled by the search client
+#[derive(Debug, Default)]
+pub struct SearchOutcome {
+    pub windows: Vec<WindowOutcome>,
+}
+
+impl SearchOutcome {
+    pub fn record(&mut self, window: DateRange, status: WindowStatus) {
+        self.windows.push(WindowOutcome { window, status });
+    }
+
+    pub fn saturated_windows(&self) -> impl Iterator<Item = &WindowOutcome> {
+        self.windows
+            .iter()
+            .filter(|outcome| matches!(outcome.status, WindowStatus::Saturated))
+    }
+
+    pub fn failed_windows(&self) -> impl Iterator<Item = &WindowOutcome> {
+        self.windows
+            .iter()
+            .filter(|outcome| matches!(outcome.status, WindowStatus::Failed { .. }))
+    }
+
+    // Saturated windows don't count as failures, fetching them again wouldn't return more
+    pub fn has_failures(&self) -> bool {
+        self.failed_windows().next().is_some()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.windows
+            .iter()
+            .all(|outcome| matches!(outcome.status, WindowStatus::Complete))
+    }
+}
+
+// A category defined as a small boolean expression over phrases, e.g. `"heat wave" AND NOT sport`.
+// NOT binds tighter than AND, which binds tighter than OR, terms next to each other are AND'd.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CategoryExpression {
+    Phrase(String),
+    And(Vec<CategoryExpression>),
+    Or(Vec<CategoryExpression>),
+    Not(Box<CategoryExpression>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ExpressionToken {
+    Phrase(String),
+    And,
+    Or,
+    Not,
+    OpenParen,
+    CloseParen,
+}
+
+impl CategoryExpression {
+    // What to search for in a category: its expression, or its name when it has none, OR'd
+    // with the keywords that aren't already part of it
+    pub fn for_category(
+        name: &str,
+        expression: Option<&CategoryExpression>,
+        keywords: &[CategoryKeyword],
+    ) -> CategoryExpression {
+        let mut alternatives = match expression {
+            Some(CategoryExpression::Or(alternatives)) => alternatives.clone(),
+            Some(expression) => vec![expression.clone()],
+            None => vec![CategoryExpression::Phrase(name.to_string())],
+        };
+        for keyword in keywords {
+            let is_known = alternatives.iter().any(|alternative| {
+                matches!(alternative, CategoryExpression::Phrase(phrase) if phrase.eq_ignore_ascii_case(&keyword.phrase))
+            });
+            if !is_known {
+                alternatives.push(CategoryExpression::Phrase(keyword.phrase.clone()));
+            }
+        }
+        match alternatives.len() {
+            1 => alternatives.remove(0),
+            _ => CategoryExpression::Or(alternatives),
+        }
+    }
+
+    pub fn parse(input: &str) -> Result<Self, CategoryExpressionError> {
+        let tokens = tokenize(input)?;
+        if tokens.is_empty() {
+            return Err(CategoryExpressionError::Empty);
+        }
+        let mut parser = ExpressionParser {
+            tokens,
+            position: 0,
+        };
+        let expression = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            return Err(CategoryExpressionError::UnexpectedToken(token.to_string()));
+        }
+        if !expression.requires_phrase() {
+            return Err(CategoryExpressionError::OnlyNegated);
+        }
+        Ok(expression)
+    }
+
+    // Whether the text (e.g. an article's title) satisfies the expression. Phrases are matched
+    // case insensitively on whole words, ignoring punctuation.
+    pub fn matches(&self, text: &str) -> bool {
+        self.matches_words(&words(text))
+    }
+
+    fn matches_words(&self, text: &[String]) -> bool {
+        match self {
+            CategoryExpression::Phrase(phrase) => {
+                let phrase = words(phrase);
+                !phrase.is_empty() && text.windows(phrase.len()).any(|window| window == phrase)
+            }
+            CategoryExpression::And(operands) => {
+                operands.iter().all(|operand| operand.matches_words(text))
+            }
+            CategoryExpression::Or(operands) => {
+                operands.iter().any(|operand| operand.matches_words(text))
+            }
+            CategoryExpression::Not(operand) => !operand.matches_words(text),
+        }
+    }
+
+    // False when the expression could be satisfied without any phrase being present, such an
+    // expression would match nearly every article
+    fn requires_phrase(&self) -> bool {
+        match self {
+            CategoryExpression::Phrase(_) => true,
+            CategoryExpression::And(operands) => operands.iter().any(Self::requires_phrase),
+            CategoryExpression::Or(operands) => operands.iter().all(Self::requires_phrase),
+            CategoryExpression::Not(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for ExpressionToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpressionToken::Phrase(phrase) => write!(f, "\"{}\"", phrase),
+            ExpressionToken::And => write!(f, "AND"),
+            ExpressionToken::Or => write!(f, "OR"),
+            ExpressionToken::Not => write!(f, "NOT"),
+            ExpressionToken::OpenParen => write!(f, "("),
+            ExpressionToken::CloseParen => write!(f, ")"),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<ExpressionToken>, CategoryExpressionError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some((position, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '(' => tokens.push(ExpressionToken::OpenParen),
+            ')' => tokens.push(ExpressionToken::CloseParen),
+            '"' => {
+                let mut phrase = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, c)) => phrase.push(c),
+                        None => return Err(CategoryExpressionError::UnterminatedQuote(position)),
+                    }
+                }
+                if phrase.trim().is_empty() {
+                    return Err(CategoryExpressionError::EmptyPhrase(position));
+                }
+                tokens.push(ExpressionToken::Phrase(phrase.trim().to_string()));
+            }
+            c => {
+                let mut word = String::from(c);
+                while let Some((_, c)) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '(' | ')' | '"') {
+                        break;
+                    }
+                    word.push(*c);
+                    chars.next();
+                }
+                tokens.push(match word.as_str() {
+                    "AND" => ExpressionToken::And,
+                    "OR" => ExpressionToken::Or,
+                    "NOT" => ExpressionToken::Not,
+                    _ => ExpressionToken::Phrase(word),
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// Recursive descent over the tokens, one method per precedence level
+struct ExpressionParser {
+    tokens: Vec<ExpressionToken>,
+    position: usize,
+}
+
+impl ExpressionParser {
+    fn peek(&self) -> Option<&ExpressionToken> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<ExpressionToken> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<CategoryExpression, CategoryExpressionError> {
+        let mut operands = vec![self.parse_and()?];
+        while self.peek() == Some(&ExpressionToken::Or) {
+            self.next();
+            operands.push(self.parse_and()?);
+        }
+        Ok(match operands.len() {
+            1 => operands.remove(0),
+            _ => CategoryExpression::Or(operands),
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<CategoryExpression, CategoryExpressionError> {
+        let mut operands = vec![self.parse_not()?];
+        loop {
+            match self.peek() {
+                Some(ExpressionToken::And) => {
+                    self.next();
+                }
+                Some(
+                    ExpressionToken::Phrase(_) | ExpressionToken::Not | ExpressionToken::OpenParen,
+                ) => {}
+                _ => break,
+            }
+            operands.push(self.parse_not()?);
+        }
+        Ok(match operands.len() {
+            1 => operands.remove(0),
+            _ => CategoryExpression::And(operands),
+        })
+    }
+
+    fn parse_not(&mut self) -> Result<CategoryExpression, CategoryExpressionError> {
+        if self.peek() == Some(&ExpressionToken::Not) {
+            self.next();
+            return Ok(CategoryExpression::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_term()
+    }
+
+    fn parse_term(&mut self) -> Result<CategoryExpression, CategoryExpressionError> {
+        match self.next() {
+            Some(ExpressionToken::Phrase(phrase)) => Ok(CategoryExpression::Phrase(phrase)),
+            Some(ExpressionToken::OpenParen) => {
+                let expression = self.parse_or()?;
+                match self.next() {
+                    Some(ExpressionToken::CloseParen) => Ok(expression),
+                    Some(token) => Err(CategoryExpressionError::UnexpectedToken(token.to_string())),
+                    None => Err(CategoryExpressionError::UnexpectedEnd),
+                }
+            }
+            Some(token) => Err(CategoryExpressionError::UnexpectedToken(token.to_string())),
+            None => Err(CategoryExpressionError::UnexpectedEnd),
+        }
+    }
+}
+
+// Lowercased alphanumeric words of the text
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CategoryExpressionError {
+    #[error("Expression is empty")]
+    Empty,
+    #[error("Quote at position {0} is never closed")]
+    UnterminatedQuote(usize),
+    #[error("Quote at position {0} holds an empty phrase")]
+    EmptyPhrase(usize),
+    #[error("Unexpected {0}")]
+    UnexpectedToken(String),
+    #[error("Expression ends unexpectedly")]
+    UnexpectedEnd,
+    #[error("Expression must require at least one phrase")]
+    OnlyNegated,
 }
 
 #[derive(Debug, Error)]
@@ -96,6 +914,12 @@ pub enum DateRangeError {
     InvalidDateRange,
 }
 
+#[derive(Debug, Error, PartialEq)]
+pub enum ArticleCursorError {
+    #[error("Invalid cursor: {0}")]
+    InvalidCursor(String),
+}
+
 // Helper function to serialize datetime
 fn serialize<S>(date: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
 where
@@ -104,3 +928,153 @@ where
     let s = date.to_rfc3339();
     serializer.serialize_str(&s)
 }
+
+fn serialize_option<S>(date: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match date {
+        Some(date) => serialize(date, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phrase(text: &str) -> CategoryExpression {
+        CategoryExpression::Phrase(text.to_string())
+    }
+
+    #[test]
+    fn test_parse_category_expression() {
+        assert_eq!(
+            CategoryExpression::parse("\"heat wave\" AND NOT sport").unwrap(),
+            CategoryExpression::And(vec![
+                phrase("heat wave"),
+                CategoryExpression::Not(Box::new(phrase("sport")))
+            ])
+        );
+        assert_eq!(
+            CategoryExpression::parse("drought OR (\"heat wave\" europe)").unwrap(),
+            CategoryExpression::Or(vec![
+                phrase("drought"),
+                CategoryExpression::And(vec![phrase("heat wave"), phrase("europe")])
+            ])
+        );
+        assert_eq!(
+            CategoryExpression::parse("a OR b AND c").unwrap(),
+            CategoryExpression::Or(vec![
+                phrase("a"),
+                CategoryExpression::And(vec![phrase("b"), phrase("c")])
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_category_expression() {
+        assert_eq!(
+            CategoryExpression::parse("  "),
+            Err(CategoryExpressionError::Empty)
+        );
+        assert_eq!(
+            CategoryExpression::parse("\"heat wave"),
+            Err(CategoryExpressionError::UnterminatedQuote(0))
+        );
+        assert_eq!(
+            CategoryExpression::parse("(drought OR flood"),
+            Err(CategoryExpressionError::UnexpectedEnd)
+        );
+        assert_eq!(
+            CategoryExpression::parse("drought OR"),
+            Err(CategoryExpressionError::UnexpectedEnd)
+        );
+        assert_eq!(
+            CategoryExpression::parse("drought )"),
+            Err(CategoryExpressionError::UnexpectedToken(")".to_string()))
+        );
+        assert_eq!(
+            CategoryExpression::parse("NOT sport"),
+            Err(CategoryExpressionError::OnlyNegated)
+        );
+    }
+
+    #[test]
+    fn test_category_expression_matches() {
+        let expression = CategoryExpression::parse("\"heat wave\" AND NOT sport").unwrap();
+        assert!(expression.matches("Europe braces for a new Heat-Wave"));
+        assert!(!expression.matches("Heat wave disrupts sport events"));
+        assert!(!expression.matches("Heatwave in Spain"));
+        assert!(!expression.matches("A wave of heat"));
+    }
+
+    #[test]
+    fn test_search_expression() {
+        let date_range = DateRange::new(
+            chrono::TimeZone::with_ymd_and_hms(&Utc, 2023, 6, 1, 0, 0, 0).unwrap(),
+            chrono::TimeZone::with_ymd_and_hms(&Utc, 2023, 6, 2, 0, 0, 0).unwrap(),
+        )
+        .unwrap();
+        let keyword = |text: &str| CategoryKeyword::new("heat".to_string(), text.to_string(), None);
+        let query = ArticleQuery::new(CountryCode::FRA, "heat".to_string(), date_range)
+            .with_keywords(vec![keyword("Heat"), keyword("canicule")]);
+        assert_eq!(
+            query.search_expression(),
+            CategoryExpression::Or(vec![phrase("heat"), phrase("canicule")])
+        );
+
+        let query = query.with_expression(Some(
+            CategoryExpression::parse("\"heat wave\" OR drought").unwrap(),
+        ));
+        assert_eq!(
+            query.search_expression(),
+            CategoryExpression::Or(vec![
+                phrase("heat wave"),
+                phrase("drought"),
+                phrase("Heat"),
+                phrase("canicule")
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_country_code() {
+        assert_eq!(parse_country_code("fr"), Some(CountryCode::FRA));
+        assert_eq!(parse_country_code("DEU"), Some(CountryCode::DEU));
+        assert_eq!(parse_country_code(" france "), Some(CountryCode::FRA));
+        assert_eq!(parse_country_code("Atlantis"), None);
+        assert_eq!(parse_country_code(""), None);
+    }
+
+    #[test]
+    fn test_article_cursor_round_trip() {
+        let cursor = ArticleCursor {
+            seen_at: chrono::TimeZone::with_ymd_and_hms(&Utc, 2023, 6, 1, 12, 30, 0).unwrap()
+                + Duration::microseconds(42),
+            id: 7,
+        };
+        let token = cursor.encode();
+        assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(ArticleCursor::decode(&token), Ok(cursor));
+        for invalid in ["", "abc", "zz", "6162"] {
+            assert!(ArticleCursor::decode(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_split_date_range() {
+        let day = |day| chrono::TimeZone::with_ymd_and_hms(&Utc, 2023, 6, day, 0, 0, 0).unwrap();
+        let windows = DateRange::new(day(1), day(8))
+            .unwrap()
+            .split(Duration::days(3));
+        let bounds: Vec<_> = windows
+            .iter()
+            .map(|range| (range.inclusive_start_date, range.inclusive_end_date))
+            .collect();
+        assert_eq!(
+            bounds,
+            vec![(day(1), day(4)), (day(4), day(7)), (day(7), day(8))]
+        );
+    }
+}
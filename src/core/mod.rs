@@ -1,3 +1,6 @@
 pub mod domain;
 pub mod ports;
+pub mod scheduler;
 pub mod service;
+pub mod sync_jobs;
+pub mod sync_worker;
@@ -1,22 +1,154 @@
-use crate::core::domain::{ArticleQuery, DateRange, NewsArticle};
+use crate::core::domain::{
+    ArticleFilter, ArticlePage, ArticleQuery, Category, CategoryExpression, CategoryKeyword,
+    CoverageMode, CoveragePoint, CoverageTimeline, DateRange, NewsArticle, PageRequest,
+    QueryReport, QueuedQuery, SearchOutcome, StoredArticles, SyncReport, SyncRunSummary,
+    SyncStatus, SyncWatermark, TonePoint, ToneTimeline,
+};
 use crate::core::service;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use isocountry::CountryCode;
+use std::sync::Arc;
+use thiserror::Error;
 use tokio::sync::mpsc;
 
+// Failures of the storage ports, adapters map the errors of their drivers onto these
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum RepositoryError {
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    // The storage can't be reached, trying again later may succeed
+    #[error("Storage unavailable: {0}")]
+    Unavailable(String),
+    // Stored data that can't be turned into domain types, e.g. an unknown country code
+    #[error("Invalid stored data: {0}")]
+    InvalidData(String),
+    #[error("Storage error: {0}")]
+    Other(String),
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum SearchClientError {
+    #[error("Throttled by the api: {0}")]
+    Throttled(String),
+    // Connection failures, timeouts and server errors
+    #[error("Transport error: {0}")]
+    Transport(String),
+    #[error("Error parsing the response: {0}")]
+    Parse(String),
+    // The api refused the request, or the query can't be expressed in its syntax
+    #[error("Request rejected: {0}")]
+    Rejected(String),
+}
+
+impl SearchClientError {
+    // Whether the same request may succeed when tried again later
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            SearchClientError::Throttled(_) | SearchClientError::Transport(_)
+        )
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ArchiveError {
+    #[error("Error reading the archive: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Corrupt archive: {0}")]
+    Corrupt(String),
+    #[error("The article receiver was dropped")]
+    Closed,
+    #[error("Archive error: {0}")]
+    Other(String),
+}
+
 #[async_trait]
 pub trait NewsService: Send + Sync {
-    // Retrieves articles from the repository with the provided categories
+    // Retrieves a page of the articles from the repository with the provided categories
     async fn get_articles_by_categories(
         &self,
         category: Vec<String>,
         date_range: DateRange,
-    ) -> Result<Vec<NewsArticle>, Box<dyn std::error::Error>>;
+        page: PageRequest,
+    ) -> Result<ArticlePage, service::NewsServiceError>;
+
+    // Like get_articles_by_categories, keeping only the articles matching the filter
+    async fn search_articles(
+        &self,
+        categories: Vec<String>,
+        date_range: DateRange,
+        filter: ArticleFilter,
+        page: PageRequest,
+    ) -> Result<ArticlePage, service::NewsServiceError>;
+
+    async fn is_valid_category(&self, category: String) -> Result<bool, service::NewsServiceError>;
+
+    // Adds a category, searched for by its name or, when given, by a boolean expression such
+    // as `"heat wave" AND NOT sport`
+    async fn add_category(
+        &self,
+        category: String,
+        expression: Option<String>,
+    ) -> Result<bool, service::NewsServiceError>;
+
+    async fn get_categories(&self) -> Result<Vec<Category>, service::NewsServiceError>;
+
+    // Renames the category everywhere it is referenced, None when it doesn't exist
+    async fn rename_category(
+        &self,
+        category: String,
+        new_name: String,
+    ) -> Result<Option<Category>, service::NewsServiceError>;
+
+    // Deletes the category along with its keywords and sync state. Its articles are kept
+    // untagged unless `purge_articles` is set, in which case the ones without another category
+    // are deleted. Returns the number of deleted articles, None when the category doesn't exist.
+    async fn delete_category(
+        &self,
+        category: String,
+        purge_articles: bool,
+    ) -> Result<Option<i32>, service::NewsServiceError>;
+
+    async fn get_countries(&self) -> Result<Vec<CountryCode>, service::NewsServiceError>;
+
+    // Adds a country to the ones synced, returns false when it already was
+    async fn add_country(&self, country: CountryCode) -> Result<bool, service::NewsServiceError>;
+
+    // Stops syncing the country and deletes its sync state and timelines. A country with stored
+    // articles can only be removed when `purge_articles` is set. Returns the number of deleted
+    // articles, None when the country wasn't synced.
+    async fn remove_country(
+        &self,
+        country: CountryCode,
+        purge_articles: bool,
+    ) -> Result<Option<i32>, service::NewsServiceError>;
+
+    // Tags the stored articles of the date range whose title matches the category
+    async fn reclassify_articles(
+        &self,
+        category: String,
+        date_range: DateRange,
+    ) -> Result<i32, service::NewsServiceError>;
+
+    // Adds a phrase searched for alongside the category, e.g. a synonym or a translation
+    async fn add_category_keyword(
+        &self,
+        keyword: CategoryKeyword,
+    ) -> Result<bool, service::NewsServiceError>;
 
-    async fn is_valid_category(&self, category: String)
-        -> Result<bool, Box<dyn std::error::Error>>;
+    async fn get_category_keywords(
+        &self,
+        category: String,
+    ) -> Result<Vec<CategoryKeyword>, service::NewsServiceError>;
 
-    async fn add_category(&self, category: String) -> Result<bool, Box<dyn std::error::Error>>;
+    async fn remove_category_keyword(
+        &self,
+        category: String,
+        phrase: String,
+    ) -> Result<bool, service::NewsServiceError>;
 
     // Fetches articles from the news search client and stores them in the repository
     // Based in an ArticleQuery
@@ -25,18 +157,125 @@ pub trait NewsService: Send + Sync {
         query: ArticleQuery,
     ) -> Result<i32, service::NewsServiceError>;
 
+    // Like fetch_and_store_articles, with the report of what couldn't be fetched or stored
+    async fn fetch_and_store_query(
+        &self,
+        query: ArticleQuery,
+    ) -> Result<QueryReport, service::NewsServiceError>;
+
+    // Fetches and stores the articles of every query, as many at once as the sync concurrency
+    // allows, and sends the report of each query to the channel as soon as it is done
+    async fn fetch_and_store_queries(
+        &self,
+        queries: Vec<ArticleQuery>,
+        reports: mpsc::Sender<QueryReport>,
+    );
+
     // Sync articles fetches all articles for the countries and categories we have in our DB
     // for the provided date range <- this is meant for a cron job type of task
-    async fn sync_articles(&self, date_range: DateRange) -> Result<i32, service::NewsServiceError>;
+    // Failing queries don't stop the others, the returned report has also been stored
+    async fn sync_articles(
+        &self,
+        date_range: DateRange,
+    ) -> Result<SyncReport, service::NewsServiceError>;
+
+    // Fetches the articles of every country and category from where the last sync of the pair
+    // left off up to now, pairs that were never synced start at initial_start. A pair's
+    // watermark only moves forward when none of its windows failed.
+    async fn sync_articles_incrementally(
+        &self,
+        initial_start: DateTime<Utc>,
+    ) -> Result<SyncReport, service::NewsServiceError>;
+
+    // The most recent sync runs first
+    async fn get_sync_runs(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<SyncRunSummary>, service::NewsServiceError>;
+
+    async fn get_sync_run(&self, id: i32) -> Result<Option<SyncReport>, service::NewsServiceError>;
+
+    // The sync status of every country and category pair, including the ones never synced
+    async fn get_sync_status(&self) -> Result<Vec<SyncStatus>, service::NewsServiceError>;
+
+    // Fetches the coverage timeline for the query from the news search client, stores it and
+    // returns the stored timeline for the query's date range
+    async fn sync_coverage_timeline(
+        &self,
+        query: ArticleQuery,
+        mode: CoverageMode,
+    ) -> Result<CoverageTimeline, service::NewsServiceError>;
+
+    async fn get_coverage_timeline(
+        &self,
+        country: CountryCode,
+        category: String,
+        mode: CoverageMode,
+        date_range: DateRange,
+    ) -> Result<CoverageTimeline, service::NewsServiceError>;
+
+    // Fetches and stores the tone timeline of the query
+    async fn sync_tone_timeline(
+        &self,
+        query: ArticleQuery,
+    ) -> Result<i32, service::NewsServiceError>;
+
+    // Syncs the tone timelines for all the countries and categories we have in our DB
+    async fn sync_tone_timelines(
+        &self,
+        date_range: DateRange,
+    ) -> Result<i32, service::NewsServiceError>;
+
+    // Retrieves the stored tone timelines of every country for the provided categories
+    async fn get_tone_timelines(
+        &self,
+        categories: Vec<String>,
+        date_range: DateRange,
+    ) -> Result<Vec<ToneTimeline>, service::NewsServiceError>;
+
+    // Stores the archived articles for the countries and categories we have in our DB,
+    // meant for backfills that don't depend on the news search client
+    async fn import_articles(
+        &self,
+        archive: Arc<dyn NewsArchive>,
+    ) -> Result<i32, service::NewsServiceError>;
 }
 
 #[async_trait]
 pub trait NewsSearchClient: Send + Sync {
+    // Sends every article matching the query to the channel, reports the parts of the
+    // date range that could not be fully enumerated
     async fn query_for_articles(
         &self,
         query: ArticleQuery,
         channel: mpsc::Sender<Vec<NewsArticle>>,
-    );
+    ) -> SearchOutcome;
+
+    async fn query_coverage_timeline(
+        &self,
+        query: &ArticleQuery,
+        mode: CoverageMode,
+    ) -> Result<Vec<CoveragePoint>, SearchClientError>;
+
+    async fn query_tone_timeline(
+        &self,
+        query: &ArticleQuery,
+    ) -> Result<Vec<TonePoint>, SearchClientError>;
+
+    // Fails when the expression can't be searched for, so it is refused before being stored
+    fn check_expression(&self, expression: &CategoryExpression) -> Result<(), SearchClientError>;
+}
+
+#[async_trait]
+pub trait NewsArchive: Send + Sync {
+    // Sends every archived article from one of the countries matching the search expression of
+    // one of the categories to the channel, returns the number of archive files read
+    async fn read_articles(
+        &self,
+        countries: Vec<CountryCode>,
+        categories: Vec<(String, CategoryExpression)>,
+        channel: mpsc::Sender<Vec<NewsArticle>>,
+    ) -> Result<i32, ArchiveError>;
 }
 
 #[async_trait]
@@ -45,20 +284,183 @@ pub trait NewsRepository: Send + Sync {
         &self,
         categories: Vec<String>,
         date_range: DateRange,
-    ) -> Result<Vec<NewsArticle>, Box<dyn std::error::Error>>;
+        page: PageRequest,
+    ) -> Result<ArticlePage, RepositoryError>;
+
+    async fn search_articles(
+        &self,
+        categories: Vec<String>,
+        date_range: DateRange,
+        filter: ArticleFilter,
+        page: PageRequest,
+    ) -> Result<ArticlePage, RepositoryError>;
 
     async fn store_articles(
         &self,
         articles: Vec<NewsArticle>,
-    ) -> Result<i32, Box<dyn std::error::Error>>;
+    ) -> Result<StoredArticles, RepositoryError>;
+
+    // Stores the report of a sync run, returns its id
+    async fn store_sync_report(&self, report: &SyncReport) -> Result<i32, RepositoryError>;
+
+    async fn get_sync_runs(&self, limit: i64) -> Result<Vec<SyncRunSummary>, RepositoryError>;
+
+    async fn get_sync_report(&self, id: i32) -> Result<Option<SyncReport>, RepositoryError>;
+
+    // The sync status of the pairs that were synced at least once, without staleness
+    async fn get_sync_statuses(&self) -> Result<Vec<SyncStatus>, RepositoryError>;
+
+    async fn add_category(
+        &self,
+        category: String,
+        expression: Option<String>,
+    ) -> Result<bool, RepositoryError>;
+
+    async fn get_category_expression(
+        &self,
+        category: String,
+    ) -> Result<Option<String>, RepositoryError>;
+
+    // Ids and titles of the articles stored for the date range
+    async fn get_article_titles(
+        &self,
+        date_range: DateRange,
+    ) -> Result<Vec<(i32, String)>, RepositoryError>;
+
+    // Adds the category to the articles, returns the number of articles that didn't have it
+    async fn add_article_categories(
+        &self,
+        category: String,
+        article_ids: Vec<i32>,
+    ) -> Result<i32, RepositoryError>;
+
+    async fn is_valid_category(&self, category: String) -> Result<bool, RepositoryError>;
+
+    async fn get_categories(&self) -> Result<Vec<String>, RepositoryError>;
+
+    // Categories with their expression, sorted by name
+    async fn get_category_definitions(&self) -> Result<Vec<Category>, RepositoryError>;
+
+    // None when the category doesn't exist, Conflict when the new name is already taken
+    async fn rename_category(
+        &self,
+        category: String,
+        new_name: String,
+    ) -> Result<Option<Category>, RepositoryError>;
+
+    // Returns the number of deleted articles, None when the category doesn't exist
+    async fn delete_category(
+        &self,
+        category: String,
+        purge_articles: bool,
+    ) -> Result<Option<i32>, RepositoryError>;
 
-    async fn add_category(&self, category: String) -> Result<bool, Box<dyn std::error::Error>>;
+    // Returns false when the category already has the phrase
+    async fn add_category_keyword(&self, keyword: CategoryKeyword)
+        -> Result<bool, RepositoryError>;
 
-    async fn is_valid_category(&self, category: String)
-        -> Result<bool, Box<dyn std::error::Error>>;
+    async fn get_category_keywords(
+        &self,
+        category: String,
+    ) -> Result<Vec<CategoryKeyword>, RepositoryError>;
+
+    async fn remove_category_keyword(
+        &self,
+        category: String,
+        phrase: String,
+    ) -> Result<bool, RepositoryError>;
+
+    async fn get_countries(&self) -> Result<Vec<CountryCode>, RepositoryError>;
+
+    // Returns false when the country was already there
+    async fn add_country(&self, country: CountryCode) -> Result<bool, RepositoryError>;
+
+    // Returns the number of deleted articles, None when the country doesn't exist. Conflict
+    // when the country still has articles and `purge_articles` isn't set.
+    async fn remove_country(
+        &self,
+        country: CountryCode,
+        purge_articles: bool,
+    ) -> Result<Option<i32>, RepositoryError>;
+
+    async fn get_sync_watermark(
+        &self,
+        country: CountryCode,
+        category: String,
+    ) -> Result<Option<SyncWatermark>, RepositoryError>;
+
+    // Stores the watermark, an older watermark never replaces a newer one
+    async fn set_sync_watermark(&self, watermark: &SyncWatermark) -> Result<(), RepositoryError>;
+
+    // Inserts the timeline's points, replacing the values of buckets already stored
+    async fn store_coverage_timeline(
+        &self,
+        timeline: &CoverageTimeline,
+    ) -> Result<i32, RepositoryError>;
+
+    async fn get_coverage_timeline(
+        &self,
+        country: CountryCode,
+        category: String,
+        mode: CoverageMode,
+        date_range: DateRange,
+    ) -> Result<CoverageTimeline, RepositoryError>;
+
+    // Inserts the timeline's points, replacing the tone of buckets already stored
+    async fn store_tone_timeline(&self, timeline: &ToneTimeline) -> Result<i32, RepositoryError>;
+
+    async fn get_tone_timelines(
+        &self,
+        categories: Vec<String>,
+        date_range: DateRange,
+    ) -> Result<Vec<ToneTimeline>, RepositoryError>;
+}
+
+// Queries shared between sync workers, possibly running in separate processes. A claimed query
+// is leased to its worker, which has to renew the lease while it works on it; once the lease
+// expires the query can be claimed again.
+#[async_trait]
+pub trait SyncJobQueue: Send + Sync {
+    // Adds the queries to the queue, returns the number of queries added
+    async fn enqueue(
+        &self,
+        queries: Vec<ArticleQuery>,
+        max_attempts: i32,
+    ) -> Result<i32, RepositoryError>;
+
+    // Claims the oldest query that is waiting or whose lease expired, None when there is none
+    async fn claim(
+        &self,
+        worker_id: &str,
+        lease: std::time::Duration,
+    ) -> Result<Option<QueuedQuery>, RepositoryError>;
+
+    // Renews the lease, returns false when the worker no longer holds it
+    async fn heartbeat(
+        &self,
+        id: i64,
+        worker_id: &str,
+        lease: std::time::Duration,
+    ) -> Result<bool, RepositoryError>;
+
+    async fn complete(
+        &self,
+        id: i64,
+        worker_id: &str,
+        inserted: i32,
+    ) -> Result<(), RepositoryError>;
+
+    // Puts the query back in the queue, or marks it as failed once it ran out of attempts
+    async fn fail(&self, id: i64, worker_id: &str, error: &str) -> Result<(), RepositoryError>;
+}
+
+// Keeps syncs started by separate processes from running at the same time
+#[async_trait]
+pub trait SyncLock: Send + Sync {
+    // Returns false when another process holds the lock
+    async fn try_lock(&self) -> Result<bool, RepositoryError>;
 
-    async fn get_categories(&self) -> Result<Vec<String>, Box<dyn std::error::Error>>;
-    async fn get_countries(&self) -> Result<Vec<CountryCode>, Box<dyn std::error::Error>>;
+    async fn unlock(&self) -> Result<(), RepositoryError>;
 }
 
 pub trait Logger: Send + Sync {
@@ -0,0 +1,203 @@
+use crate::core::domain::SyncReport;
+use crate::core::ports::{self, SyncLock};
+use crate::core::service::NewsServiceError;
+use chrono::{DateTime, Duration, Utc};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use thiserror::Error;
+
+// How often a scheduler waiting for its next run checks whether it should stop
+const STOP_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+#[derive(Debug, Clone)]
+pub enum SyncSchedule {
+    // A run every interval, counted from the start of the previous run
+    Every(Duration),
+    // Runs at the times matching a cron expression with a seconds field, e.g. "0 */15 * * * *"
+    Cron(Box<cron::Schedule>),
+}
+
+#[derive(Error, Debug)]
+pub enum SyncScheduleError {
+    #[error("Invalid interval: {0}")]
+    InvalidInterval(String),
+    #[error("Invalid cron expression: {0}")]
+    InvalidCron(String),
+}
+
+impl SyncSchedule {
+    // Either an interval such as "90s", "15m" or "1h", or a cron expression
+    pub fn parse(schedule: &str) -> Result<Self, SyncScheduleError> {
+        let schedule = schedule.trim();
+        if !schedule.contains(' ') {
+            // The unit may be any character, splitting on a byte offset would panic on one
+            // that takes more than a byte
+            let unit_start = schedule
+                .char_indices()
+                .last()
+                .map(|(index, _)| index)
+                .unwrap_or(0);
+            let (amount, unit) = schedule.split_at(unit_start);
+            let amount: i64 = amount
+                .parse()
+                .map_err(|_| SyncScheduleError::InvalidInterval(schedule.to_string()))?;
+            let interval = match unit {
+                "s" => Duration::seconds(amount),
+                "m" => Duration::minutes(amount),
+                "h" => Duration::hours(amount),
+                _ => return Err(SyncScheduleError::InvalidInterval(schedule.to_string())),
+            };
+            if amount <= 0 {
+                return Err(SyncScheduleError::InvalidInterval(schedule.to_string()));
+            }
+            return Ok(SyncSchedule::Every(interval));
+        }
+        cron::Schedule::from_str(schedule)
+            .map(|schedule| SyncSchedule::Cron(Box::new(schedule)))
+            .map_err(|e| SyncScheduleError::InvalidCron(format!("{}: {}", schedule, e)))
+    }
+
+    // When the next run should start, runs missed while the previous one was running are
+    // skipped rather than caught up on
+    pub fn next_run(
+        &self,
+        last_start: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        match self {
+            SyncSchedule::Every(interval) => Some(
+                last_start
+                    .map(|start| std::cmp::max(start + *interval, now))
+                    .unwrap_or(now),
+            ),
+            SyncSchedule::Cron(schedule) => schedule.after(&now).next(),
+        }
+    }
+}
+
+// Runs incremental syncs on a schedule. The lock keeps runs of schedulers in separate
+// processes from overlapping.
+pub struct SyncScheduler {
+    logger: Box<dyn ports::Logger>,
+    news_service: Arc<dyn ports::NewsService>,
+    lock: Arc<dyn SyncLock>,
+    schedule: SyncSchedule,
+    // How far back countries and categories that were never synced start
+    initial_lookback: Duration,
+}
+
+impl SyncScheduler {
+    pub fn new(
+        logger: Box<dyn ports::Logger>,
+        news_service: Arc<dyn ports::NewsService>,
+        lock: Arc<dyn SyncLock>,
+        schedule: SyncSchedule,
+        initial_lookback: Duration,
+    ) -> Self {
+        Self {
+            logger,
+            news_service,
+            lock,
+            schedule,
+            initial_lookback,
+        }
+    }
+
+    // Runs until `stop` is set. A run in progress is left to finish the queries in flight,
+    // the news service should share the same stop signal.
+    pub async fn run(&self, stop: Arc<AtomicBool>) {
+        let mut last_start = None;
+        while let Some(next_run) = self.schedule.next_run(last_start, Utc::now()) {
+            self.logger
+                .info(&format!("Next sync at {}", next_run.to_rfc3339()));
+            if !wait_until(next_run, &stop).await {
+                break;
+            }
+            last_start = Some(Utc::now());
+            if let Err(e) = self.run_once().await {
+                self.logger.error(&format!("Scheduled sync failed: {}", e));
+            }
+        }
+        self.logger.info("Sync scheduler has stopped");
+    }
+
+    // Runs a single incremental sync unless another one holds the lock, in which case None
+    // is returned
+    pub async fn run_once(&self) -> Result<Option<SyncReport>, NewsServiceError> {
+        if !self.lock.try_lock().await? {
+            self.logger
+                .warn("Skipping the scheduled sync, another sync is still running");
+            return Ok(None);
+        }
+        let synced = self
+            .news_service
+            .sync_articles_incrementally(Utc::now() - self.initial_lookback)
+            .await;
+        if let Err(e) = self.lock.unlock().await {
+            self.logger
+                .error(&format!("Failed to release the sync lock: {}", e));
+        }
+
+        let report = synced?;
+        self.logger.info(&format!(
+            "Scheduled sync finished, {} of {} queries failed",
+            report.failed_queries().count(),
+            report.queries.len()
+        ));
+        Ok(Some(report))
+    }
+}
+
+// Returns false when `stop` was set before the time was reached
+async fn wait_until(time: DateTime<Utc>, stop: &AtomicBool) -> bool {
+    loop {
+        if stop.load(Ordering::SeqCst) {
+            return false;
+        }
+        let remaining = match (time - Utc::now()).to_std() {
+            Ok(remaining) if !remaining.is_zero() => remaining,
+            _ => return true,
+        };
+        tokio::time::sleep(std::cmp::min(remaining, STOP_CHECK_INTERVAL)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parse_sync_schedule() {
+        assert!(matches!(
+            SyncSchedule::parse("15m").unwrap(),
+            SyncSchedule::Every(interval) if interval == Duration::minutes(15)
+        ));
+        assert!(matches!(
+            SyncSchedule::parse("0 */15 * * * *").unwrap(),
+            SyncSchedule::Cron(_)
+        ));
+        for invalid in ["", "15", "0m", "-5m", "15d", "15分", "5µ", "µ", "* * *"] {
+            assert!(SyncSchedule::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_next_run() {
+        let at = |hour, minute| Utc.with_ymd_and_hms(2023, 6, 1, hour, minute, 0).unwrap();
+
+        let every = SyncSchedule::parse("15m").unwrap();
+        assert_eq!(every.next_run(None, at(10, 7)), Some(at(10, 7)));
+        assert_eq!(every.next_run(Some(at(10, 0)), at(10, 7)), Some(at(10, 15)));
+        // A run that took longer than the interval is followed by the next one right away
+        assert_eq!(
+            every.next_run(Some(at(10, 0)), at(10, 40)),
+            Some(at(10, 40))
+        );
+
+        let cron = SyncSchedule::parse("0 */15 * * * *").unwrap();
+        assert_eq!(cron.next_run(None, at(10, 7)), Some(at(10, 15)));
+        assert_eq!(cron.next_run(Some(at(10, 0)), at(10, 40)), Some(at(10, 45)));
+    }
+}
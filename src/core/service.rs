@@ -1,13 +1,37 @@
-use crate::core::domain::{ArticleQuery, DateRange, NewsArticle};
+use crate::core::domain::{
+    ArticleFilter, ArticlePage, ArticleQuery, Category, CategoryExpression,
+    CategoryExpressionError, CategoryKeyword, CoverageMode, CoverageTimeline, DateRange,
+    DateRangeError, PageRequest, QueryReport, SyncMode, SyncReport, SyncRunSummary, SyncStatus,
+    SyncWatermark, ToneTimeline, WindowStatus,
+};
 use crate::core::ports;
+use crate::core::ports::{ArchiveError, RepositoryError, SearchClientError};
 use async_trait::async_trait;
-use std::fmt;
+use chrono::{DateTime, Utc};
+use futures_util::future;
+use futures_util::stream::{self, StreamExt};
+use isocountry::CountryCode;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use thiserror::Error;
 use tokio::sync::mpsc;
 
+// Number of queries a sync runs at the same time, the search client still spaces out the
+// requests themselves to respect the api's rate limit
+pub const DEFAULT_SYNC_CONCURRENCY: usize = 4;
+// GDELT indexes articles up to about 15 minutes after it saw them, incremental syncs start this
+// long before the watermark to pick up the ones that were indexed after the previous sync
+pub const INDEXING_LAG_MINUTES: i64 = 15;
+
 pub struct NewsService {
     logger: Box<dyn ports::Logger>,
     news_repository: Box<dyn ports::NewsRepository>,
     news_search_client: std::sync::Arc<dyn ports::NewsSearchClient>,
+    sync_concurrency: usize,
+    // Once set, syncs finish the queries in flight and skip the rest
+    stop: Arc<AtomicBool>,
 }
 
 impl NewsService {
@@ -20,92 +44,715 @@ impl NewsService {
             logger,
             news_repository,
             news_search_client,
+            sync_concurrency: DEFAULT_SYNC_CONCURRENCY,
+            stop: Arc::new(AtomicBool::new(false)),
         }
     }
-}
 
-#[async_trait]
-impl ports::NewsService for NewsService {
-    async fn get_articles_by_categories(
+    pub fn with_sync_concurrency(mut self, sync_concurrency: usize) -> Self {
+        self.sync_concurrency = sync_concurrency.max(1);
+        self
+    }
+
+    pub fn with_stop_signal(mut self, stop: Arc<AtomicBool>) -> Self {
+        self.stop = stop;
+        self
+    }
+
+    // Runs the queries with at most sync_concurrency of them in flight, queries that haven't
+    // started when the stop signal is set are skipped
+    async fn run_queries<'a, T, F, Fut>(&'a self, queries: Vec<ArticleQuery>, run: F) -> Vec<T>
+    where
+        F: Fn(ArticleQuery) -> Fut,
+        Fut: Future<Output = T> + 'a,
+    {
+        let stop = self.stop.clone();
+        stream::iter(queries)
+            .take_while(move |_| future::ready(!stop.load(Ordering::SeqCst)))
+            .map(run)
+            .buffer_unordered(self.sync_concurrency)
+            .collect()
+            .await
+    }
+
+    // Syncs the articles of every query, then logs and stores the report of the run
+    async fn run_sync(
         &self,
-        categories: Vec<String>,
+        mode: SyncMode,
         date_range: DateRange,
-    ) -> Result<Vec<NewsArticle>, Box<dyn std::error::Error>> {
-        self.news_repository
-            .get_articles_by_categories(categories, date_range)
-            .await
+        queries: Vec<ArticleQuery>,
+    ) -> SyncReport {
+        let started_at = Utc::now();
+        let num_queries = queries.len();
+        let queries = self
+            .run_queries(queries, |query| self.sync_query(query, mode))
+            .await;
+        if queries.len() < num_queries {
+            self.logger.warn(&format!(
+                "Sync stopped early, skipped {} of {} queries",
+                num_queries - queries.len(),
+                num_queries
+            ));
+        }
+        let mut report = SyncReport {
+            id: None,
+            mode,
+            started_at,
+            finished_at: Utc::now(),
+            inclusive_start_date: date_range.inclusive_start_date,
+            inclusive_end_date: date_range.inclusive_end_date,
+            queries,
+        };
+        for failed in report.failed_queries() {
+            self.logger.error(&format!(
+                "Failed to sync {} in {}: {}",
+                failed.category,
+                failed.country.alpha3(),
+                failed.errors.join(", ")
+            ));
+        }
+        self.logger.info(&format!(
+            "Synced {} queries: fetched {} articles, inserted {}, {} duplicates, {} failed windows",
+            report.queries.len(),
+            report.fetched(),
+            report.inserted(),
+            report.duplicates(),
+            report.failed_windows()
+        ));
+        match self.news_repository.store_sync_report(&report).await {
+            Ok(id) => report.id = Some(id),
+            Err(e) => self
+                .logger
+                .error(&format!("Failed to store the sync report: {}", e)),
+        }
+        report
+    }
+
+    // Errors end up in the report instead of being returned so one query can't stop a run
+    async fn sync_query(&self, query: ArticleQuery, mode: SyncMode) -> QueryReport {
+        let started = Instant::now();
+        let mut report = QueryReport::new(
+            query.source_country,
+            query.category.clone(),
+            &query.date_range,
+        );
+        let result = match mode {
+            SyncMode::Full => self.fetch_and_store(query).await,
+            SyncMode::Incremental => self.sync_from_watermark(query).await,
+        };
+        match result {
+            Ok(query_report) => report = query_report,
+            Err(e) => report.errors.push(e.to_string()),
+        }
+        report.duration_ms = started.elapsed().as_millis() as i64;
+        report
     }
 
-    async fn is_valid_category(
+    // Moves the start of the query to the pair's watermark, minus the indexing lag, which is
+    // advanced to the end of the query once every window of it was fetched and stored
+    async fn sync_from_watermark(
         &self,
-        category: String,
-    ) -> Result<bool, Box<dyn std::error::Error>> {
-        self.news_repository.is_valid_category(category).await
+        mut query: ArticleQuery,
+    ) -> Result<QueryReport, NewsServiceError> {
+        if let Some(watermark) = self
+            .news_repository
+            .get_sync_watermark(query.source_country, query.category.clone())
+            .await?
+        {
+            let start = watermark.synced_until - chrono::Duration::minutes(INDEXING_LAG_MINUTES);
+            match DateRange::new(start, query.date_range.inclusive_end_date) {
+                Ok(date_range) => query.date_range = date_range,
+                Err(_) => {
+                    let mut report =
+                        QueryReport::new(query.source_country, query.category, &query.date_range);
+                    report.inclusive_start_date = start;
+                    return Ok(report);
+                }
+            }
+        }
+        let watermark = SyncWatermark {
+            country: query.source_country,
+            category: query.category.clone(),
+            synced_until: query.date_range.inclusive_end_date,
+        };
+        let report = self.fetch_and_store(query).await?;
+        if report.is_complete() {
+            self.news_repository.set_sync_watermark(&watermark).await?;
+        } else {
+            self.logger.warn(&format!(
+                "Keeping the {} watermark of {} at {}, the sync was incomplete",
+                watermark.category,
+                watermark.country.alpha3(),
+                report.inclusive_start_date
+            ));
+        }
+        Ok(report)
     }
 
-    async fn add_category(&self, category: String) -> Result<bool, Box<dyn std::error::Error>> {
-        self.news_repository.add_category(category).await
+    // Fills in the category's expression and keywords unless the caller already provided them
+    async fn with_category_definition(
+        &self,
+        mut query: ArticleQuery,
+    ) -> Result<ArticleQuery, NewsServiceError> {
+        if query.expression.is_none() {
+            let expression = self.get_category_expression(&query.category).await?;
+            query = query.with_expression(expression);
+        }
+        if query.keywords.is_empty() {
+            let keywords = self
+                .news_repository
+                .get_category_keywords(query.category.clone())
+                .await?;
+            query = query.with_keywords(keywords);
+        }
+        Ok(query)
     }
 
-    async fn fetch_and_store_articles(&self, query: ArticleQuery) -> Result<i32, NewsServiceError> {
+    async fn get_category_expression(
+        &self,
+        category: &str,
+    ) -> Result<Option<CategoryExpression>, NewsServiceError> {
+        match self
+            .news_repository
+            .get_category_expression(category.to_string())
+            .await?
+        {
+            Some(expression) => Ok(Some(CategoryExpression::parse(&expression)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn check_search_expression(
+        &self,
+        expression: &CategoryExpression,
+    ) -> Result<(), NewsServiceError> {
+        self.news_search_client
+            .check_expression(expression)
+            .map_err(|e| NewsServiceError::UnsupportedExpression(e.to_string()))
+    }
+
+    // The category's expression, or its name, OR'd with its keywords
+    async fn category_search_expression(
+        &self,
+        category: &str,
+    ) -> Result<CategoryExpression, NewsServiceError> {
+        let expression = self.get_category_expression(category).await?;
+        let keywords = self
+            .news_repository
+            .get_category_keywords(category.to_string())
+            .await?;
+        Ok(CategoryExpression::for_category(
+            category,
+            expression.as_ref(),
+            &keywords,
+        ))
+    }
+
+    async fn fetch_and_store(&self, query: ArticleQuery) -> Result<QueryReport, NewsServiceError> {
         let is_valid = self
             .news_repository
             .is_valid_category(query.category.clone())
-            .await
-            .map_err(NewsServiceError::RepositoryError)?;
+            .await?;
         if !is_valid {
             self.logger.debug("category is not valid");
             return Err(NewsServiceError::InvalidCategory(query.category.clone()));
         }
+        let query = self.with_category_definition(query).await?;
+        let mut report = QueryReport::new(
+            query.source_country,
+            query.category.clone(),
+            &query.date_range,
+        );
         self.logger.debug("starting fetch and store articles");
         let (channel, mut rx) = mpsc::channel(10000);
         let client = std::sync::Arc::clone(&self.news_search_client);
-        tokio::spawn(async move {
-            client.query_for_articles(query, channel).await;
-        });
-        let mut count = 0;
+        let search = tokio::spawn(async move { client.query_for_articles(query, channel).await });
         while let Some(articles) = rx.recv().await {
+            let num_articles = articles.len() as i32;
+            report.fetched += num_articles;
             match self.news_repository.store_articles(articles).await {
-                Ok(num) => count += num,
-                Err(e) => self.logger.error(&format!("Error storing articles: {}", e)),
+                Ok(stored) => report.add_stored(stored),
+                Err(e) => {
+                    self.logger.error(&format!("Error storing articles: {}", e));
+                    report
+                        .errors
+                        .push(format!("Failed to store {} articles: {}", num_articles, e));
+                }
             }
         }
-        Ok(count)
+        match search.await {
+            Ok(outcome) => {
+                for saturated in outcome.saturated_windows() {
+                    self.logger.warn(&format!(
+                        "Could not fetch every article between {} and {}",
+                        saturated.window.inclusive_start_date, saturated.window.inclusive_end_date
+                    ));
+                }
+                for failed in outcome.failed_windows() {
+                    if let WindowStatus::Failed { attempts, error } = &failed.status {
+                        let message = format!(
+                            "Gave up fetching articles between {} and {} after {} attempts: {}",
+                            failed.window.inclusive_start_date,
+                            failed.window.inclusive_end_date,
+                            attempts,
+                            error
+                        );
+                        self.logger.error(&message);
+                        report.failed_windows += 1;
+                        report.errors.push(message);
+                    }
+                }
+            }
+            Err(e) => {
+                self.logger.error(&format!("Article search failed: {}", e));
+                report.errors.push(format!("Article search failed: {}", e));
+            }
+        }
+        Ok(report)
     }
+}
 
-    async fn sync_articles(&self, date_range: DateRange) -> Result<i32, NewsServiceError> {
+#[async_trait]
+impl ports::NewsService for NewsService {
+    async fn get_articles_by_categories(
+        &self,
+        categories: Vec<String>,
+        date_range: DateRange,
+        page: PageRequest,
+    ) -> Result<ArticlePage, NewsServiceError> {
+        Ok(self
+            .news_repository
+            .get_articles_by_categories(categories, date_range, page)
+            .await?)
+    }
+
+    async fn search_articles(
+        &self,
+        categories: Vec<String>,
+        date_range: DateRange,
+        filter: ArticleFilter,
+        page: PageRequest,
+    ) -> Result<ArticlePage, NewsServiceError> {
+        Ok(self
+            .news_repository
+            .search_articles(categories, date_range, filter, page)
+            .await?)
+    }
+
+    async fn is_valid_category(&self, category: String) -> Result<bool, NewsServiceError> {
+        Ok(self.news_repository.is_valid_category(category).await?)
+    }
+
+    async fn add_category(
+        &self,
+        category: String,
+        expression: Option<String>,
+    ) -> Result<bool, NewsServiceError> {
+        if let Some(expression) = &expression {
+            let expression = CategoryExpression::parse(expression)?;
+            self.check_search_expression(&CategoryExpression::for_category(
+                &category,
+                Some(&expression),
+                &[],
+            ))?;
+        }
+        let expression = expression.map(|expression| expression.trim().to_string());
+        Ok(self
+            .news_repository
+            .add_category(category, expression)
+            .await?)
+    }
+
+    async fn get_categories(&self) -> Result<Vec<Category>, NewsServiceError> {
+        Ok(self.news_repository.get_category_definitions().await?)
+    }
+
+    async fn rename_category(
+        &self,
+        category: String,
+        new_name: String,
+    ) -> Result<Option<Category>, NewsServiceError> {
+        let renamed = self
+            .news_repository
+            .rename_category(category.clone(), new_name.trim().to_string())
+            .await?;
+        if let Some(renamed) = &renamed {
+            self.logger.info(&format!(
+                "Renamed category {} to {}",
+                category, renamed.name
+            ));
+        }
+        Ok(renamed)
+    }
+
+    async fn delete_category(
+        &self,
+        category: String,
+        purge_articles: bool,
+    ) -> Result<Option<i32>, NewsServiceError> {
+        let num_deleted = self
+            .news_repository
+            .delete_category(category.clone(), purge_articles)
+            .await?;
+        if let Some(num_deleted) = num_deleted {
+            self.logger.info(&format!(
+                "Deleted category {} and {} of its articles",
+                category, num_deleted
+            ));
+        }
+        Ok(num_deleted)
+    }
+
+    async fn get_countries(&self) -> Result<Vec<CountryCode>, NewsServiceError> {
+        Ok(self.news_repository.get_countries().await?)
+    }
+
+    async fn add_country(&self, country: CountryCode) -> Result<bool, NewsServiceError> {
+        let added = self.news_repository.add_country(country).await?;
+        if added {
+            self.logger
+                .info(&format!("Added country {}", country.alpha3()));
+        }
+        Ok(added)
+    }
+
+    async fn remove_country(
+        &self,
+        country: CountryCode,
+        purge_articles: bool,
+    ) -> Result<Option<i32>, NewsServiceError> {
+        let num_deleted = self
+            .news_repository
+            .remove_country(country, purge_articles)
+            .await?;
+        if let Some(num_deleted) = num_deleted {
+            self.logger.info(&format!(
+                "Removed country {} and {} of its articles",
+                country.alpha3(),
+                num_deleted
+            ));
+        }
+        Ok(num_deleted)
+    }
+
+    async fn reclassify_articles(
+        &self,
+        category: String,
+        date_range: DateRange,
+    ) -> Result<i32, NewsServiceError> {
+        if !self
+            .news_repository
+            .is_valid_category(category.clone())
+            .await?
+        {
+            return Err(NewsServiceError::InvalidCategory(category));
+        }
+        let expression = self.category_search_expression(&category).await?;
+        let matching_ids: Vec<i32> = self
+            .news_repository
+            .get_article_titles(date_range)
+            .await?
+            .into_iter()
+            .filter(|(_, title)| expression.matches(title))
+            .map(|(id, _)| id)
+            .collect();
+        let num_tagged = self
+            .news_repository
+            .add_article_categories(category.clone(), matching_ids)
+            .await?;
+        self.logger.info(&format!(
+            "Tagged {} stored articles with {}",
+            num_tagged, category
+        ));
+        Ok(num_tagged)
+    }
+
+    async fn add_category_keyword(
+        &self,
+        keyword: CategoryKeyword,
+    ) -> Result<bool, NewsServiceError> {
+        if !self
+            .news_repository
+            .is_valid_category(keyword.category.clone())
+            .await?
+        {
+            return Err(NewsServiceError::InvalidCategory(keyword.category));
+        }
+        // Keywords are OR'd with the category's expression, which the search client may not
+        // be able to express, e.g. `(a AND b) OR keyword`
+        let expression = self.get_category_expression(&keyword.category).await?;
+        let mut keywords = self
+            .news_repository
+            .get_category_keywords(keyword.category.clone())
+            .await?;
+        keywords.push(keyword.clone());
+        self.check_search_expression(&CategoryExpression::for_category(
+            &keyword.category,
+            expression.as_ref(),
+            &keywords,
+        ))?;
+        Ok(self.news_repository.add_category_keyword(keyword).await?)
+    }
+
+    async fn get_category_keywords(
+        &self,
+        category: String,
+    ) -> Result<Vec<CategoryKeyword>, NewsServiceError> {
+        Ok(self.news_repository.get_category_keywords(category).await?)
+    }
+
+    async fn remove_category_keyword(
+        &self,
+        category: String,
+        phrase: String,
+    ) -> Result<bool, NewsServiceError> {
+        Ok(self
+            .news_repository
+            .remove_category_keyword(category, phrase)
+            .await?)
+    }
+
+    async fn fetch_and_store_articles(&self, query: ArticleQuery) -> Result<i32, NewsServiceError> {
+        let report = self.fetch_and_store(query).await?;
+        Ok(report.inserted)
+    }
+
+    async fn fetch_and_store_query(
+        &self,
+        query: ArticleQuery,
+    ) -> Result<QueryReport, NewsServiceError> {
+        self.fetch_and_store(query).await
+    }
+
+    async fn fetch_and_store_queries(
+        &self,
+        queries: Vec<ArticleQuery>,
+        reports: mpsc::Sender<QueryReport>,
+    ) {
+        self.run_queries(queries, |query| {
+            let reports = reports.clone();
+            async move {
+                let report = self.sync_query(query, SyncMode::Full).await;
+                // Nobody is waiting for the report any more, the caller was cancelled
+                let _ = reports.send(report).await;
+            }
+        })
+        .await;
+    }
+
+    async fn sync_articles(&self, date_range: DateRange) -> Result<SyncReport, NewsServiceError> {
+        let categories = self.news_repository.get_categories().await?;
+        let countries = self.news_repository.get_countries().await?;
+        let queries = ArticleQuery::build_queries(categories, countries, date_range.clone());
+        Ok(self.run_sync(SyncMode::Full, date_range, queries).await)
+    }
+
+    async fn sync_articles_incrementally(
+        &self,
+        initial_start: DateTime<Utc>,
+    ) -> Result<SyncReport, NewsServiceError> {
+        let categories = self.news_repository.get_categories().await?;
+        let countries = self.news_repository.get_countries().await?;
+        let date_range = match DateRange::new(initial_start, Utc::now()) {
+            Ok(date_range) => date_range,
+            Err(e) => return Err(NewsServiceError::InvalidDateRange(e)),
+        };
+        let queries = ArticleQuery::build_queries(categories, countries, date_range.clone());
+        Ok(self
+            .run_sync(SyncMode::Incremental, date_range, queries)
+            .await)
+    }
+
+    async fn get_sync_runs(&self, limit: i64) -> Result<Vec<SyncRunSummary>, NewsServiceError> {
+        Ok(self.news_repository.get_sync_runs(limit).await?)
+    }
+
+    async fn get_sync_run(&self, id: i32) -> Result<Option<SyncReport>, NewsServiceError> {
+        Ok(self.news_repository.get_sync_report(id).await?)
+    }
+
+    async fn get_sync_status(&self) -> Result<Vec<SyncStatus>, NewsServiceError> {
+        let categories = self.news_repository.get_categories().await?;
+        let countries = self.news_repository.get_countries().await?;
+        let known = self.news_repository.get_sync_statuses().await?;
+        let now = Utc::now();
+        let mut statuses = Vec::new();
+        for country in countries {
+            for category in &categories {
+                let mut status = known
+                    .iter()
+                    .find(|status| status.country == country && &status.category == category)
+                    .cloned()
+                    .unwrap_or_else(|| SyncStatus::new(country, category.clone()));
+                status.staleness_seconds = status
+                    .last_success_at
+                    .map(|last_success_at| (now - last_success_at).num_seconds());
+                statuses.push(status);
+            }
+        }
+        Ok(statuses)
+    }
+
+    async fn sync_coverage_timeline(
+        &self,
+        query: ArticleQuery,
+        mode: CoverageMode,
+    ) -> Result<CoverageTimeline, NewsServiceError> {
+        let is_valid = self
+            .news_repository
+            .is_valid_category(query.category.clone())
+            .await?;
+        if !is_valid {
+            return Err(NewsServiceError::InvalidCategory(query.category.clone()));
+        }
+        let query = self.with_category_definition(query).await?;
+        let points = self
+            .news_search_client
+            .query_coverage_timeline(&query, mode)
+            .await?;
+        self.logger.debug(&format!(
+            "Fetched {} {} coverage points for {} in {}",
+            points.len(),
+            mode.as_str(),
+            query.category,
+            query.source_country.alpha3()
+        ));
+        let timeline = CoverageTimeline {
+            country: query.source_country,
+            category: query.category,
+            mode,
+            points,
+        };
+        self.news_repository
+            .store_coverage_timeline(&timeline)
+            .await?;
+        Ok(self
+            .news_repository
+            .get_coverage_timeline(timeline.country, timeline.category, mode, query.date_range)
+            .await?)
+    }
+
+    async fn get_coverage_timeline(
+        &self,
+        country: CountryCode,
+        category: String,
+        mode: CoverageMode,
+        date_range: DateRange,
+    ) -> Result<CoverageTimeline, NewsServiceError> {
+        Ok(self
+            .news_repository
+            .get_coverage_timeline(country, category, mode, date_range)
+            .await?)
+    }
+
+    async fn sync_tone_timeline(&self, query: ArticleQuery) -> Result<i32, NewsServiceError> {
+        let is_valid = self
+            .news_repository
+            .is_valid_category(query.category.clone())
+            .await?;
+        if !is_valid {
+            return Err(NewsServiceError::InvalidCategory(query.category.clone()));
+        }
+        let query = self.with_category_definition(query).await?;
+        let points = self.news_search_client.query_tone_timeline(&query).await?;
+        let timeline = ToneTimeline {
+            country: query.source_country,
+            category: query.category,
+            points,
+        };
+        Ok(self.news_repository.store_tone_timeline(&timeline).await?)
+    }
+
+    async fn sync_tone_timelines(&self, date_range: DateRange) -> Result<i32, NewsServiceError> {
         let categories = self.news_repository.get_categories().await?;
         let countries = self.news_repository.get_countries().await?;
         let queries = ArticleQuery::build_queries(categories, countries, date_range);
-        let mut num_articles = 0;
-        for query in queries {
-            let num = self.fetch_and_store_articles(query).await?;
-            num_articles += num;
+        let mut num_points = 0;
+        let results = self
+            .run_queries(queries, |query| async move {
+                let description =
+                    format!("{} in {}", query.category, query.source_country.alpha3());
+                self.sync_tone_timeline(query)
+                    .await
+                    .map_err(|e| format!("Failed to sync the tone of {}: {}", description, e))
+            })
+            .await;
+        for result in results {
+            match result {
+                Ok(num) => num_points += num,
+                Err(e) => self.logger.error(&e),
+            }
         }
         self.logger
-            .info(&format!("Synced {} articles", num_articles));
-        Ok(num_articles)
+            .info(&format!("Synced {} tone timeline points", num_points));
+        Ok(num_points)
     }
-}
 
-pub enum NewsServiceError {
-    InvalidCategory(String),
-    RepositoryError(Box<dyn std::error::Error>),
-}
+    async fn get_tone_timelines(
+        &self,
+        categories: Vec<String>,
+        date_range: DateRange,
+    ) -> Result<Vec<ToneTimeline>, NewsServiceError> {
+        Ok(self
+            .news_repository
+            .get_tone_timelines(categories, date_range)
+            .await?)
+    }
 
-impl fmt::Display for NewsServiceError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            NewsServiceError::InvalidCategory(category) => {
-                write!(f, "Invalid category: {}", category)
+    async fn import_articles(
+        &self,
+        archive: std::sync::Arc<dyn ports::NewsArchive>,
+    ) -> Result<i32, NewsServiceError> {
+        let mut categories = Vec::new();
+        for category in self.news_repository.get_categories().await? {
+            let expression = self.category_search_expression(&category).await?;
+            categories.push((category, expression));
+        }
+        let countries = self.news_repository.get_countries().await?;
+        self.logger.info(&format!(
+            "Importing archived articles for {} categories and {} countries",
+            categories.len(),
+            countries.len()
+        ));
+        let (channel, mut rx) = mpsc::channel(100);
+        let read =
+            tokio::spawn(
+                async move { archive.read_articles(countries, categories, channel).await },
+            );
+        let mut count = 0;
+        while let Some(articles) = rx.recv().await {
+            match self.news_repository.store_articles(articles).await {
+                Ok(stored) => count += stored.inserted,
+                Err(e) => self.logger.error(&format!("Error storing articles: {}", e)),
             }
-            NewsServiceError::RepositoryError(err) => write!(f, "Repository error: {}", err),
         }
+        match read.await {
+            Ok(Ok(files_read)) => self.logger.info(&format!(
+                "Imported {} articles from {} archive files",
+                count, files_read
+            )),
+            Ok(Err(e)) => return Err(NewsServiceError::ArchiveError(e)),
+            Err(e) => return Err(ArchiveError::Other(e.to_string()).into()),
+        }
+        Ok(count)
     }
 }
 
-impl From<Box<dyn std::error::Error>> for NewsServiceError {
-    fn from(err: Box<dyn std::error::Error>) -> NewsServiceError {
-        NewsServiceError::RepositoryError(err)
-    }
+#[derive(Error, Debug)]
+pub enum NewsServiceError {
+    #[error("Invalid category: {0}")]
+    InvalidCategory(String),
+    #[error("Invalid category expression: {0}")]
+    InvalidExpression(#[from] CategoryExpressionError),
+    // A valid expression the search client can't search for
+    #[error("Unsupported category expression: {0}")]
+    UnsupportedExpression(String),
+    #[error("Invalid date range: {0}")]
+    InvalidDateRange(#[from] DateRangeError),
+    #[error("Repository error: {0}")]
+    RepositoryError(#[from] RepositoryError),
+    #[error("Search client error: {0}")]
+    SearchClientError(#[from] SearchClientError),
+    #[error("Archive error: {0}")]
+    ArchiveError(#[from] ArchiveError),
 }
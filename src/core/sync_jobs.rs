@@ -0,0 +1,148 @@
+use crate::core::domain::{ArticleQuery, JobStatus, QueryReport, SyncJob, SyncRequest};
+use crate::core::ports;
+use crate::core::service::NewsServiceError;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio::task::AbortHandle;
+
+// Runs syncs requested through the api in the background and keeps track of their progress.
+// Jobs only live in memory, they are lost when the process stops.
+pub struct SyncJobs {
+    logger: Box<dyn ports::Logger>,
+    news_service: Arc<dyn ports::NewsService>,
+    state: Arc<Mutex<JobsState>>,
+}
+
+#[derive(Default)]
+struct JobsState {
+    last_id: i64,
+    jobs: HashMap<i64, SyncJob>,
+    tasks: HashMap<i64, AbortHandle>,
+}
+
+impl SyncJobs {
+    pub fn new(logger: Box<dyn ports::Logger>, news_service: Arc<dyn ports::NewsService>) -> Self {
+        Self {
+            logger,
+            news_service,
+            state: Arc::new(Mutex::new(JobsState::default())),
+        }
+    }
+
+    // Validates the categories and starts the job, returns its id
+    pub async fn submit(&self, request: SyncRequest) -> Result<i64, NewsServiceError> {
+        for category in &request.categories {
+            if !self
+                .news_service
+                .is_valid_category(category.clone())
+                .await?
+            {
+                return Err(NewsServiceError::InvalidCategory(category.clone()));
+            }
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.last_id += 1;
+        let id = state.last_id;
+        state.jobs.insert(id, SyncJob::new(id, request.clone()));
+        let task = tokio::spawn(run_job(
+            id,
+            request,
+            self.logger.clone_box(),
+            self.news_service.clone(),
+            self.state.clone(),
+        ));
+        state.tasks.insert(id, task.abort_handle());
+        self.logger.info(&format!("Queued sync job {}", id));
+        Ok(id)
+    }
+
+    pub fn get(&self, id: i64) -> Option<SyncJob> {
+        self.state.lock().unwrap().jobs.get(&id).cloned()
+    }
+
+    // Stops the job unless it already finished, returns the job as it is afterwards
+    pub fn cancel(&self, id: i64) -> Option<SyncJob> {
+        let mut state = self.state.lock().unwrap();
+        let task = state.tasks.remove(&id);
+        let job = state.jobs.get_mut(&id)?;
+        if !job.status.is_finished() {
+            job.status = JobStatus::Cancelled;
+            job.finished_at = Some(Utc::now());
+            if let Some(task) = task {
+                task.abort();
+            }
+            self.logger.info(&format!("Cancelled sync job {}", id));
+        }
+        Some(job.clone())
+    }
+}
+
+// Fetches the articles of the request through the service, which runs as many queries at once
+// as its sync concurrency allows, recording the progress after each query. Aborting the task
+// drops the report and article channels, which stops the searches still running.
+async fn run_job(
+    id: i64,
+    request: SyncRequest,
+    logger: Box<dyn ports::Logger>,
+    news_service: Arc<dyn ports::NewsService>,
+    state: Arc<Mutex<JobsState>>,
+) {
+    let update = |change: &dyn Fn(&mut SyncJob)| {
+        let mut state = state.lock().unwrap();
+        if let Some(job) = state.jobs.get_mut(&id) {
+            // A cancelled job keeps the state it had when it was cancelled
+            if job.status != JobStatus::Cancelled {
+                change(job);
+            }
+        }
+    };
+    update(&|job| {
+        job.status = JobStatus::Running;
+        job.started_at = Some(Utc::now());
+    });
+
+    let queries =
+        ArticleQuery::build_queries(request.categories, request.countries, request.date_range);
+    let (channel, mut rx) = mpsc::channel::<QueryReport>(queries.len().max(1));
+    let record_reports = async {
+        while let Some(report) = rx.recv().await {
+            let error = (!report.is_complete()).then(|| {
+                format!(
+                    "Failed to sync {} in {}: {}",
+                    report.category,
+                    report.country.alpha3(),
+                    report.errors.join(", ")
+                )
+            });
+            if let Some(e) = &error {
+                logger.error(&format!("Sync job {}: {}", id, e));
+            }
+            update(&|job| {
+                job.completed_queries += 1;
+                job.inserted += report.inserted;
+                if let Some(e) = &error {
+                    job.failed_queries += 1;
+                    job.errors.push(e.clone());
+                }
+            });
+        }
+    };
+    tokio::join!(
+        news_service.fetch_and_store_queries(queries, channel),
+        record_reports
+    );
+
+    update(&|job| {
+        job.status = if job.total_queries > 0 && job.failed_queries == job.total_queries {
+            JobStatus::Failed
+        } else {
+            JobStatus::Completed
+        };
+        job.finished_at = Some(Utc::now());
+    });
+    state.lock().unwrap().tasks.remove(&id);
+    logger.info(&format!("Sync job {} finished", id));
+}
@@ -0,0 +1,160 @@
+use crate::core::domain::{ArticleQuery, DateRange};
+use crate::core::ports::{self, RepositoryError, SyncJobQueue};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const DEFAULT_MAX_ATTEMPTS: i32 = 3;
+
+#[derive(Debug, Clone)]
+pub struct SyncWorkerConfig {
+    // How long a claimed job stays with the worker without a heartbeat
+    pub lease: Duration,
+    pub heartbeat_interval: Duration,
+    // How long to wait before looking for work again when the queue is empty
+    pub poll_interval: Duration,
+}
+
+impl Default for SyncWorkerConfig {
+    fn default() -> Self {
+        Self {
+            lease: Duration::from_secs(300),
+            heartbeat_interval: Duration::from_secs(60),
+            poll_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+// Claims queries from the shared queue and fetches their articles, several workers can run
+// against the same queue
+pub struct SyncWorker {
+    logger: Box<dyn ports::Logger>,
+    news_service: Arc<dyn ports::NewsService>,
+    queue: Arc<dyn SyncJobQueue>,
+    worker_id: String,
+    config: SyncWorkerConfig,
+}
+
+impl SyncWorker {
+    pub fn new(
+        logger: Box<dyn ports::Logger>,
+        news_service: Arc<dyn ports::NewsService>,
+        queue: Arc<dyn SyncJobQueue>,
+        worker_id: String,
+        config: SyncWorkerConfig,
+    ) -> Self {
+        Self {
+            logger,
+            news_service,
+            queue,
+            worker_id,
+            config,
+        }
+    }
+
+    // Works through the queue until `stop` is set, the job in progress is finished first
+    pub async fn run(&self, stop: Arc<AtomicBool>) {
+        self.logger
+            .info(&format!("Sync worker {} has started", self.worker_id));
+        while !stop.load(Ordering::SeqCst) {
+            match self.run_once().await {
+                Ok(true) => continue,
+                Ok(false) => {}
+                Err(e) => self
+                    .logger
+                    .error(&format!("Sync worker {}: {}", self.worker_id, e)),
+            }
+            tokio::time::sleep(self.config.poll_interval).await;
+        }
+        self.logger
+            .info(&format!("Sync worker {} has stopped", self.worker_id));
+    }
+
+    // Claims and runs a single job, returns false when the queue had nothing to claim
+    pub async fn run_once(&self) -> Result<bool, RepositoryError> {
+        let job = match self.queue.claim(&self.worker_id, self.config.lease).await? {
+            Some(job) => job,
+            None => return Ok(false),
+        };
+        let id = job.id;
+        let description = format!(
+            "{} in {} (attempt {} of {})",
+            job.query.category,
+            job.query.source_country.alpha3(),
+            job.attempts,
+            job.max_attempts
+        );
+        self.logger
+            .info(&format!("Claimed sync job {}: {}", id, description));
+
+        let fetch = self.news_service.fetch_and_store_query(job.query);
+        tokio::pin!(fetch);
+        let mut heartbeat = tokio::time::interval(self.config.heartbeat_interval);
+        // The first tick completes immediately, the claim already started the lease
+        heartbeat.tick().await;
+        let result = loop {
+            tokio::select! {
+                result = &mut fetch => break result,
+                _ = heartbeat.tick() => {
+                    let renewed = self
+                        .queue
+                        .heartbeat(id, &self.worker_id, self.config.lease)
+                        .await;
+                    match renewed {
+                        Ok(true) => {}
+                        // Another worker may pick the job up again, finishing it is harmless
+                        // since stored articles are deduplicated
+                        Ok(false) => self
+                            .logger
+                            .warn(&format!("Lost the lease on sync job {}", id)),
+                        Err(e) => self.logger.error(&format!(
+                            "Failed to renew the lease on sync job {}: {}",
+                            id, e
+                        )),
+                    }
+                }
+            }
+        };
+
+        // Windows that failed or articles that couldn't be stored fail the job so it is tried
+        // again, the articles already stored are deduplicated then
+        let result = match result {
+            Ok(report) if report.is_complete() => Ok(report.inserted),
+            Ok(report) => Err(report.errors.join(", ")),
+            Err(e) => Err(e.to_string()),
+        };
+        match result {
+            Ok(inserted) => {
+                self.queue.complete(id, &self.worker_id, inserted).await?;
+                self.logger.info(&format!(
+                    "Finished sync job {}, inserted {} articles",
+                    id, inserted
+                ));
+            }
+            Err(e) => {
+                self.queue.fail(id, &self.worker_id, &e).await?;
+                self.logger
+                    .error(&format!("Sync job {} failed: {}: {}", id, description, e));
+            }
+        }
+        Ok(true)
+    }
+}
+
+// Queues a query per country, category and window of the date range, smaller windows spread
+// a backfill over more workers
+pub async fn enqueue_backfill(
+    queue: &dyn SyncJobQueue,
+    categories: Vec<String>,
+    countries: Vec<isocountry::CountryCode>,
+    date_range: DateRange,
+    window: chrono::Duration,
+    max_attempts: i32,
+) -> Result<i32, RepositoryError> {
+    let queries = date_range
+        .split(window)
+        .into_iter()
+        .flat_map(|range| ArticleQuery::build_queries(categories.clone(), countries.clone(), range))
+        .collect();
+    queue.enqueue(queries, max_attempts).await
+}
@@ -0,0 +1,153 @@
+use crate::core::domain::{ArticleCursorError, DateRangeError};
+use crate::core::ports::RepositoryError;
+use crate::core::service::NewsServiceError;
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+use std::fmt;
+
+// Errors returned by the api, sent as application/problem+json bodies (RFC 7807)
+#[derive(Debug)]
+pub enum ApiError {
+    // The request is malformed, e.g. a missing parameter or a date that can't be parsed
+    InvalidRequest(String),
+    InvalidDateRange(DateRangeError),
+    InvalidCategory(String),
+    UnknownCategory(String),
+    NotFound(String),
+    Conflict(String),
+    // The database or another service the api depends on can't be reached
+    Unavailable,
+    Internal,
+}
+
+#[derive(Serialize)]
+struct ProblemDetails {
+    #[serde(rename = "type")]
+    problem_type: &'static str,
+    title: &'static str,
+    status: u16,
+    detail: String,
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::InvalidRequest(_)
+            | ApiError::InvalidDateRange(_)
+            | ApiError::InvalidCategory(_) => StatusCode::BAD_REQUEST,
+            ApiError::UnknownCategory(_) | ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            ApiError::InvalidRequest(_) => "Invalid request",
+            ApiError::InvalidDateRange(_) => "Invalid date range",
+            ApiError::InvalidCategory(_) => "Invalid category",
+            ApiError::UnknownCategory(_) => "Unknown category",
+            ApiError::NotFound(_) => "Not found",
+            ApiError::Conflict(_) => "Conflict",
+            ApiError::Unavailable => "Service unavailable",
+            ApiError::Internal => "Internal server error",
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ApiError::InvalidRequest(detail)
+            | ApiError::NotFound(detail)
+            | ApiError::Conflict(detail) => write!(f, "{}", detail),
+            ApiError::InvalidDateRange(e) => write!(f, "{}", e),
+            ApiError::InvalidCategory(category) => {
+                write!(f, "\"{}\" is not a valid category", category)
+            }
+            ApiError::UnknownCategory(category) => {
+                write!(f, "Category \"{}\" does not exist", category)
+            }
+            ApiError::Unavailable => write!(f, "The service is unavailable, try again later"),
+            ApiError::Internal => write!(f, "Something went wrong"),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let problem = ProblemDetails {
+            problem_type: "about:blank",
+            title: self.title(),
+            status: status.as_u16(),
+            detail: self.to_string(),
+        };
+        (
+            status,
+            [(header::CONTENT_TYPE, "application/problem+json")],
+            Json(problem),
+        )
+            .into_response()
+    }
+}
+
+// Errors the client can do something about keep their details, the others are logged by the
+// handler and hidden behind a generic message
+impl From<NewsServiceError> for ApiError {
+    fn from(e: NewsServiceError) -> Self {
+        match e {
+            NewsServiceError::InvalidCategory(category) => ApiError::UnknownCategory(category),
+            NewsServiceError::InvalidExpression(e) => ApiError::InvalidRequest(e.to_string()),
+            NewsServiceError::UnsupportedExpression(e) => ApiError::InvalidRequest(e),
+            NewsServiceError::InvalidDateRange(e) => ApiError::InvalidDateRange(e),
+            NewsServiceError::RepositoryError(RepositoryError::NotFound(what)) => {
+                ApiError::NotFound(format!("{} not found", what))
+            }
+            NewsServiceError::RepositoryError(RepositoryError::Conflict(_)) => {
+                ApiError::Conflict("The request conflicts with the stored data".to_string())
+            }
+            NewsServiceError::RepositoryError(RepositoryError::Unavailable(_)) => {
+                ApiError::Unavailable
+            }
+            NewsServiceError::SearchClientError(e) if e.is_retryable() => ApiError::Unavailable,
+            NewsServiceError::RepositoryError(_)
+            | NewsServiceError::SearchClientError(_)
+            | NewsServiceError::ArchiveError(_) => ApiError::Internal,
+        }
+    }
+}
+
+impl From<DateRangeError> for ApiError {
+    fn from(e: DateRangeError) -> Self {
+        ApiError::InvalidDateRange(e)
+    }
+}
+
+impl From<ArticleCursorError> for ApiError {
+    fn from(e: ArticleCursorError) -> Self {
+        ApiError::InvalidRequest(e.to_string())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::InvalidRequest(rejection.body_text())
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::InvalidRequest(rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiError::InvalidRequest(rejection.body_text())
+    }
+}
@@ -1 +1,2 @@
+pub mod api_error;
 pub mod rest;
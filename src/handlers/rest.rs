@@ -1,9 +1,12 @@
-use crate::core::{domain, ports};
+use crate::core::sync_jobs::SyncJobs;
+use crate::core::{domain, ports, service};
+use crate::handlers::api_error::ApiError;
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
 use axum::extract::Query;
 use axum::{
     extract::{Path, State},
-    response::IntoResponse,
-    routing::get,
+    http::StatusCode,
+    routing::{delete, get, patch, post},
     Json, Router,
 };
 use chrono::{DateTime, NaiveDate, NaiveTime, TimeZone, Utc};
@@ -17,12 +20,14 @@ use tower_http::trace::TraceLayer;
 pub struct RestHandler {
     logger: Box<dyn ports::Logger>,
     news_service: Arc<dyn ports::NewsService>,
+    sync_jobs: Arc<SyncJobs>,
     port: String,
 }
 
 struct AppState {
     logger: Box<dyn ports::Logger>,
     news_service: Arc<dyn ports::NewsService>,
+    sync_jobs: Arc<SyncJobs>,
 }
 
 impl Clone for AppState {
@@ -30,30 +35,62 @@ impl Clone for AppState {
         Self {
             logger: self.logger.clone_box(),
             news_service: self.news_service.clone(),
+            sync_jobs: self.sync_jobs.clone(),
         }
     }
 }
 
+impl AppState {
+    // Errors the client can't do anything about are logged before being hidden behind a generic
+    // response
+    fn api_error(&self, e: impl Into<service::NewsServiceError>) -> ApiError {
+        let e = e.into();
+        let message = e.to_string();
+        let api_error = ApiError::from(e);
+        if api_error.status().is_server_error() {
+            self.logger.error(&message);
+        }
+        api_error
+    }
+}
+
 impl RestHandler {
     pub fn new(
         news_service: Arc<dyn ports::NewsService>,
         logger: Box<dyn ports::Logger>,
         port: String,
     ) -> Self {
+        let sync_jobs = Arc::new(SyncJobs::new(logger.clone_box(), news_service.clone()));
         Self {
             logger,
             news_service,
+            sync_jobs,
             port,
         }
     }
 
     pub async fn start(self) -> Result<(), Box<dyn std::error::Error>> {
         self.logger.info("Rest rest handler is starting");
+        let app = self.router();
+
+        let addr = SocketAddr::from(([0, 0, 0, 0], self.port.parse()?));
+
+        axum::Server::bind(&addr)
+            .serve(app.into_make_service())
+            .await
+            .unwrap();
+
+        Ok(())
+    }
+
+    // The routes of the api, separate from start so they can be exercised without a server
+    pub fn router(&self) -> Router {
         let app_state = AppState {
             logger: self.logger.clone_box(),
             news_service: self.news_service.clone(),
+            sync_jobs: self.sync_jobs.clone(),
         };
-        let app = Router::new()
+        Router::new()
             .route(
                 "/is-valid-category/:category_name",
                 get(is_valid_category_handler),
@@ -62,17 +99,29 @@ impl RestHandler {
                 "/get-articles-by-category",
                 get(get_articles_by_categories_handler),
             )
+            .route(
+                "/categories",
+                get(get_categories_handler).post(create_category_handler),
+            )
+            .route(
+                "/categories/:category_name",
+                patch(rename_category_handler).delete(delete_category_handler),
+            )
+            .route(
+                "/countries",
+                get(get_countries_handler).post(add_country_handler),
+            )
+            .route("/countries/:country", delete(remove_country_handler))
+            .route("/sync-runs", get(get_sync_runs_handler))
+            .route("/sync-runs/:id", get(get_sync_run_handler))
+            .route("/sync-status", get(get_sync_status_handler))
+            .route("/sync", post(create_sync_job_handler))
+            .route(
+                "/jobs/:id",
+                get(get_sync_job_handler).delete(cancel_sync_job_handler),
+            )
             .layer(TraceLayer::new_for_http())
-            .with_state(app_state);
-
-        let addr = SocketAddr::from(([0, 0, 0, 0], self.port.parse()?));
-
-        axum::Server::bind(&addr)
-            .serve(app.into_make_service())
-            .await
-            .unwrap();
-
-        Ok(())
+            .with_state(app_state)
     }
 }
 
@@ -88,21 +137,287 @@ struct CategoryValidityResponse {
 
 async fn is_valid_category_handler(
     State(app_state): State<AppState>,
-    category_query: Path<String>,
-) -> impl IntoResponse {
+    category_query: Result<Path<String>, PathRejection>,
+) -> Result<Json<CategoryValidityResponse>, ApiError> {
+    let Path(category) = category_query?;
     let is_valid = app_state
         .news_service
-        .is_valid_category(category_query.to_string())
+        .is_valid_category(category)
+        .await
+        .map_err(|e| app_state.api_error(e))?;
+    Ok(Json(CategoryValidityResponse { is_valid }))
+}
+
+async fn get_categories_handler(
+    State(app_state): State<AppState>,
+) -> Result<Json<Vec<domain::Category>>, ApiError> {
+    let categories = app_state
+        .news_service
+        .get_categories()
         .await
-        .unwrap_or(false);
-    Json(CategoryValidityResponse { is_valid })
+        .map_err(|e| app_state.api_error(e))?;
+    Ok(Json(categories))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCategoryRequest {
+    pub name: String,
+    // Searched for instead of the name, e.g. `"heat wave" AND NOT sport`
+    pub expression: Option<String>,
+}
+
+async fn create_category_handler(
+    State(app_state): State<AppState>,
+    body: Result<Json<CreateCategoryRequest>, JsonRejection>,
+) -> Result<(StatusCode, Json<domain::Category>), ApiError> {
+    let Json(body) = body?;
+    let name = body.name.trim().to_string();
+    if name.is_empty() {
+        return Err(ApiError::InvalidCategory(name));
+    }
+    let expression = body
+        .expression
+        .map(|expression| expression.trim().to_string());
+    let added = app_state
+        .news_service
+        .add_category(name.clone(), expression.clone())
+        .await
+        .map_err(|e| app_state.api_error(e))?;
+    if !added {
+        return Err(ApiError::Conflict(format!(
+            "Category \"{}\" already exists",
+            name
+        )));
+    }
+    Ok((
+        StatusCode::CREATED,
+        Json(domain::Category { name, expression }),
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RenameCategoryRequest {
+    pub name: String,
+}
+
+async fn rename_category_handler(
+    State(app_state): State<AppState>,
+    category: Result<Path<String>, PathRejection>,
+    body: Result<Json<RenameCategoryRequest>, JsonRejection>,
+) -> Result<Json<domain::Category>, ApiError> {
+    let Path(category) = category?;
+    let Json(body) = body?;
+    let new_name = body.name.trim().to_string();
+    if new_name.is_empty() {
+        return Err(ApiError::InvalidCategory(new_name));
+    }
+    match app_state
+        .news_service
+        .rename_category(category.clone(), new_name.clone())
+        .await
+    {
+        Ok(Some(renamed)) => Ok(Json(renamed)),
+        Ok(None) => Err(ApiError::UnknownCategory(category)),
+        Err(service::NewsServiceError::RepositoryError(ports::RepositoryError::Conflict(_))) => {
+            Err(ApiError::Conflict(format!(
+                "Category \"{}\" already exists",
+                new_name
+            )))
+        }
+        Err(e) => Err(app_state.api_error(e)),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteCategoryQuery {
+    // Also deletes the articles that don't belong to another category
+    #[serde(default)]
+    pub purge_articles: bool,
+}
+
+#[derive(Serialize)]
+struct CategoryDeletedResponse {
+    deleted_articles: i32,
+}
+
+async fn delete_category_handler(
+    State(app_state): State<AppState>,
+    category: Result<Path<String>, PathRejection>,
+    query: Result<Query<DeleteCategoryQuery>, QueryRejection>,
+) -> Result<Json<CategoryDeletedResponse>, ApiError> {
+    let Path(category) = category?;
+    let Query(query) = query?;
+    match app_state
+        .news_service
+        .delete_category(category.clone(), query.purge_articles)
+        .await
+        .map_err(|e| app_state.api_error(e))?
+    {
+        Some(deleted_articles) => Ok(Json(CategoryDeletedResponse { deleted_articles })),
+        None => Err(ApiError::UnknownCategory(category)),
+    }
+}
+
+async fn get_countries_handler(
+    State(app_state): State<AppState>,
+) -> Result<Json<Vec<domain::Country>>, ApiError> {
+    let mut countries: Vec<domain::Country> = app_state
+        .news_service
+        .get_countries()
+        .await
+        .map_err(|e| app_state.api_error(e))?
+        .into_iter()
+        .map(domain::Country::from)
+        .collect();
+    countries.sort_by_key(|country| country.alpha_3);
+    Ok(Json(countries))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddCountryRequest {
+    // Alpha 2 or alpha 3 code or name
+    pub country: String,
+    // Starts a sync job fetching the articles of every category from this date up to now
+    #[serde(default, deserialize_with = "deserialize_optional")]
+    pub backfill_from: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct CountryAddedResponse {
+    #[serde(flatten)]
+    country: domain::Country,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backfill_job_id: Option<i64>,
+    // The country stays added when its backfill couldn't be started, the backfill can be
+    // submitted as a sync job instead
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backfill_error: Option<String>,
+}
+
+async fn add_country_handler(
+    State(app_state): State<AppState>,
+    body: Result<Json<AddCountryRequest>, JsonRejection>,
+) -> Result<(StatusCode, Json<CountryAddedResponse>), ApiError> {
+    let Json(body) = body?;
+    let country = domain::parse_country_code(&body.country)
+        .ok_or_else(|| ApiError::InvalidRequest(format!("Unknown country {}", body.country)))?;
+    let backfill_range = match body.backfill_from {
+        Some(start) => Some(domain::DateRange::new(start, Utc::now())?),
+        None => None,
+    };
+    let added = app_state
+        .news_service
+        .add_country(country)
+        .await
+        .map_err(|e| app_state.api_error(e))?;
+    if !added {
+        return Err(ApiError::Conflict(format!(
+            "Country {} is already synced",
+            country.alpha3()
+        )));
+    }
+
+    let mut backfill_job_id = None;
+    let mut backfill_error = None;
+    if let Some(date_range) = backfill_range {
+        match submit_backfill(&app_state, country, date_range).await {
+            Ok(job_id) => backfill_job_id = job_id,
+            Err(e) => {
+                app_state.logger.error(&format!(
+                    "Failed to start the backfill of {}: {}",
+                    country.alpha3(),
+                    e
+                ));
+                backfill_error = Some(e.to_string());
+            }
+        }
+    }
+    Ok((
+        StatusCode::CREATED,
+        Json(CountryAddedResponse {
+            country: country.into(),
+            backfill_job_id,
+            backfill_error,
+        }),
+    ))
+}
+
+// Submits a sync job fetching the articles of every category for a newly added country,
+// without categories there is nothing to fetch yet
+async fn submit_backfill(
+    app_state: &AppState,
+    country: isocountry::CountryCode,
+    date_range: domain::DateRange,
+) -> Result<Option<i64>, service::NewsServiceError> {
+    let categories: Vec<String> = app_state
+        .news_service
+        .get_categories()
+        .await?
+        .into_iter()
+        .map(|category| category.name)
+        .collect();
+    if categories.is_empty() {
+        return Ok(None);
+    }
+    let request = domain::SyncRequest {
+        countries: vec![country],
+        categories,
+        date_range,
+    };
+    Ok(Some(app_state.sync_jobs.submit(request).await?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RemoveCountryQuery {
+    // Also deletes the articles of the country, which otherwise keep it from being removed
+    #[serde(default)]
+    pub purge_articles: bool,
+}
+
+#[derive(Serialize)]
+struct CountryRemovedResponse {
+    deleted_articles: i32,
+}
+
+async fn remove_country_handler(
+    State(app_state): State<AppState>,
+    country: Result<Path<String>, PathRejection>,
+    query: Result<Query<RemoveCountryQuery>, QueryRejection>,
+) -> Result<Json<CountryRemovedResponse>, ApiError> {
+    let Path(country) = country?;
+    let Query(query) = query?;
+    let not_found = || ApiError::NotFound(format!("Country {} is not synced", country));
+    let code = domain::parse_country_code(&country).ok_or_else(not_found)?;
+    match app_state
+        .news_service
+        .remove_country(code, query.purge_articles)
+        .await
+    {
+        Ok(Some(deleted_articles)) => Ok(Json(CountryRemovedResponse { deleted_articles })),
+        Ok(None) => Err(not_found()),
+        Err(service::NewsServiceError::RepositoryError(ports::RepositoryError::Conflict(_))) => {
+            Err(ApiError::Conflict(format!(
+                "Country {} has stored articles, set purge_articles to delete them",
+                code.alpha3()
+            )))
+        }
+        Err(e) => Err(app_state.api_error(e)),
+    }
 }
 
 #[derive(Serialize)]
 struct ArticleResponse {
     articles: Vec<domain::NewsArticle>,
+    // Passed as `cursor` to get the next page, null on the last page
+    next_cursor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tone_timelines: Option<Vec<domain::ToneTimeline>>,
 }
 
+// Number of articles returned when the request doesn't ask for a number
+const DEFAULT_ARTICLES_LIMIT: i64 = 100;
+const MAX_ARTICLES_LIMIT: i64 = 1000;
+
 #[derive(Debug, Deserialize)]
 pub struct ArticleQuery {
     pub categories: String,
@@ -110,34 +425,264 @@ pub struct ArticleQuery {
     pub inclusive_start_date: DateTime<Utc>,
     #[serde(deserialize_with = "deserialize")]
     pub inclusive_end_date: DateTime<Utc>,
+    // Adds the tone timelines of the categories to the response
+    #[serde(default)]
+    pub include_tone: bool,
+    pub limit: Option<i64>,
+    // Newest articles first unless "asc"
+    #[serde(default)]
+    pub sort: domain::SortOrder,
+    // The next_cursor of the previous page
+    pub cursor: Option<String>,
+    // Comma separated lists, alpha 2 or alpha 3 codes or names for the countries
+    pub countries: Option<String>,
+    pub languages: Option<String>,
+    pub domains: Option<String>,
+    pub exclude_domains: Option<String>,
+    pub title_contains: Option<String>,
+}
+
+impl ArticleQuery {
+    fn filter(&self) -> Result<domain::ArticleFilter, ApiError> {
+        let countries = split_list(&self.countries)
+            .into_iter()
+            .map(|country| {
+                domain::parse_country_code(&country)
+                    .ok_or_else(|| ApiError::InvalidRequest(format!("Unknown country {}", country)))
+            })
+            .collect::<Result<Vec<_>, ApiError>>()?;
+        Ok(domain::ArticleFilter {
+            countries,
+            languages: split_list(&self.languages),
+            domains: split_list(&self.domains),
+            excluded_domains: split_list(&self.exclude_domains),
+            title_contains: self
+                .title_contains
+                .as_ref()
+                .map(|text| text.trim().to_string())
+                .filter(|text| !text.is_empty()),
+        })
+    }
+}
+
+fn split_list(list: &Option<String>) -> Vec<String> {
+    list.iter()
+        .flat_map(|list| list.split(','))
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .collect()
 }
 
 async fn get_articles_by_categories_handler(
     State(app_state): State<AppState>,
-    Query(query): Query<ArticleQuery>,
-) -> impl IntoResponse {
-    let categories: Vec<String> = query.categories.split(',').map(|s| s.to_string()).collect();
-    // TODO validate categories
-
-    let date_range = domain::DateRange::new(query.inclusive_start_date, query.inclusive_end_date)
-        .map_err(|e| {
-            // TODO handle error
-            app_state.logger.error(&e.to_string());
-            return e;
-        })
-        .unwrap();
+    query: Result<Query<ArticleQuery>, QueryRejection>,
+) -> Result<Json<ArticleResponse>, ApiError> {
+    let Query(query) = query?;
+    let categories: Vec<String> = query
+        .categories
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .collect();
+    for category in &categories {
+        if category.is_empty() {
+            return Err(ApiError::InvalidCategory(category.clone()));
+        }
+        let is_valid = app_state
+            .news_service
+            .is_valid_category(category.clone())
+            .await
+            .map_err(|e| app_state.api_error(e))?;
+        if !is_valid {
+            return Err(ApiError::UnknownCategory(category.clone()));
+        }
+    }
+    let date_range = domain::DateRange::new(query.inclusive_start_date, query.inclusive_end_date)?;
+    let filter = query.filter()?;
+    let page = domain::PageRequest {
+        limit: query
+            .limit
+            .unwrap_or(DEFAULT_ARTICLES_LIMIT)
+            .clamp(1, MAX_ARTICLES_LIMIT),
+        order: query.sort,
+        after: query
+            .cursor
+            .as_deref()
+            .map(domain::ArticleCursor::decode)
+            .transpose()?,
+    };
 
     app_state.logger.info(&format!(
-        "Getting articles for categories {:?} and date range {:?}",
-        categories, date_range
+        "Getting articles for categories {:?}, date range {:?} and filter {:?}",
+        categories, date_range, filter
     ));
 
-    let articles = app_state
+    let tone_timelines = if query.include_tone {
+        Some(
+            app_state
+                .news_service
+                .get_tone_timelines(categories.clone(), date_range.clone())
+                .await
+                .map_err(|e| app_state.api_error(e))?,
+        )
+    } else {
+        None
+    };
+
+    let page = app_state
+        .news_service
+        .search_articles(categories, date_range, filter, page)
+        .await
+        .map_err(|e| app_state.api_error(e))?;
+    Ok(Json(ArticleResponse {
+        articles: page.articles,
+        next_cursor: page.next_cursor.map(|cursor| cursor.encode()),
+        tone_timelines,
+    }))
+}
+
+// Number of sync runs returned when the request doesn't ask for a number
+const DEFAULT_SYNC_RUNS_LIMIT: i64 = 20;
+const MAX_SYNC_RUNS_LIMIT: i64 = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct SyncRunsQuery {
+    pub limit: Option<i64>,
+}
+
+async fn get_sync_runs_handler(
+    State(app_state): State<AppState>,
+    query: Result<Query<SyncRunsQuery>, QueryRejection>,
+) -> Result<Json<Vec<domain::SyncRunSummary>>, ApiError> {
+    let Query(query) = query?;
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_SYNC_RUNS_LIMIT)
+        .clamp(1, MAX_SYNC_RUNS_LIMIT);
+    let runs = app_state
+        .news_service
+        .get_sync_runs(limit)
+        .await
+        .map_err(|e| app_state.api_error(e))?;
+    Ok(Json(runs))
+}
+
+async fn get_sync_run_handler(
+    State(app_state): State<AppState>,
+    id: Result<Path<i32>, PathRejection>,
+) -> Result<Json<domain::SyncReport>, ApiError> {
+    let Path(id) = id?;
+    match app_state
         .news_service
-        .get_articles_by_categories(categories, date_range)
+        .get_sync_run(id)
         .await
-        .unwrap_or(Vec::new());
-    Json(ArticleResponse { articles })
+        .map_err(|e| app_state.api_error(e))?
+    {
+        Some(report) => Ok(Json(report)),
+        None => Err(ApiError::NotFound(format!(
+            "Sync run {} does not exist",
+            id
+        ))),
+    }
+}
+
+async fn get_sync_status_handler(
+    State(app_state): State<AppState>,
+) -> Result<Json<Vec<domain::SyncStatus>>, ApiError> {
+    let statuses = app_state
+        .news_service
+        .get_sync_status()
+        .await
+        .map_err(|e| app_state.api_error(e))?;
+    Ok(Json(statuses))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SyncJobRequest {
+    // Alpha 2 or alpha 3 codes or names
+    pub countries: Vec<String>,
+    pub categories: Vec<String>,
+    #[serde(deserialize_with = "deserialize")]
+    pub inclusive_start_date: DateTime<Utc>,
+    #[serde(deserialize_with = "deserialize")]
+    pub inclusive_end_date: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+struct SyncJobCreatedResponse {
+    job_id: i64,
+}
+
+async fn create_sync_job_handler(
+    State(app_state): State<AppState>,
+    body: Result<Json<SyncJobRequest>, JsonRejection>,
+) -> Result<(StatusCode, Json<SyncJobCreatedResponse>), ApiError> {
+    let Json(body) = body?;
+    if body.countries.is_empty() || body.categories.is_empty() {
+        return Err(ApiError::InvalidRequest(
+            "At least one country and one category are required".to_string(),
+        ));
+    }
+    let mut countries = Vec::new();
+    for code in &body.countries {
+        match domain::parse_country_code(code) {
+            Some(country) => countries.push(country),
+            None => {
+                return Err(ApiError::InvalidRequest(format!(
+                    "Unknown country {}",
+                    code
+                )))
+            }
+        }
+    }
+    let date_range = domain::DateRange::new(body.inclusive_start_date, body.inclusive_end_date)?;
+    let request = domain::SyncRequest {
+        countries,
+        categories: body.categories,
+        date_range,
+    };
+    match app_state.sync_jobs.submit(request).await {
+        Ok(job_id) => Ok((
+            StatusCode::ACCEPTED,
+            Json(SyncJobCreatedResponse { job_id }),
+        )),
+        // The categories are part of the body rather than the path
+        Err(service::NewsServiceError::InvalidCategory(category)) => {
+            Err(ApiError::InvalidCategory(category))
+        }
+        Err(e) => Err(app_state.api_error(e)),
+    }
+}
+
+async fn get_sync_job_handler(
+    State(app_state): State<AppState>,
+    id: Result<Path<i64>, PathRejection>,
+) -> Result<Json<domain::SyncJob>, ApiError> {
+    let Path(id) = id?;
+    match app_state.sync_jobs.get(id) {
+        Some(job) => Ok(Json(job)),
+        None => Err(ApiError::NotFound(format!(
+            "Sync job {} does not exist",
+            id
+        ))),
+    }
+}
+
+async fn cancel_sync_job_handler(
+    State(app_state): State<AppState>,
+    id: Result<Path<i64>, PathRejection>,
+) -> Result<Json<domain::SyncJob>, ApiError> {
+    let Path(id) = id?;
+    match app_state.sync_jobs.cancel(id) {
+        Some(job) if job.status == domain::JobStatus::Cancelled => Ok(Json(job)),
+        Some(job) => Err(ApiError::Conflict(format!(
+            "Sync job {} already finished as {:?}",
+            id, job.status
+        ))),
+        None => Err(ApiError::NotFound(format!(
+            "Sync job {} does not exist",
+            id
+        ))),
+    }
 }
 
 // Helper function to deserialize datetime
@@ -146,6 +691,19 @@ where
     D: Deserializer<'de>,
 {
     let s = String::deserialize(deserializer)?;
-    let date = NaiveDate::parse_from_str(&s, "%Y-%m-%d").map_err(Error::custom)?;
+    parse_date(&s).map_err(Error::custom)
+}
+
+fn deserialize_optional<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| parse_date(&s).map_err(Error::custom))
+        .transpose()
+}
+
+fn parse_date(s: &str) -> Result<DateTime<Utc>, chrono::ParseError> {
+    let date = NaiveDate::parse_from_str(s, "%Y-%m-%d")?;
     Ok(Utc.from_utc_datetime(&date.and_time(NaiveTime::from_hms_opt(0, 0, 0).unwrap())))
 }
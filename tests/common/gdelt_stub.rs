@@ -0,0 +1,211 @@
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::collections::HashMap;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+
+// The text the real api answers with when it is called too often
+pub const THROTTLE_RESPONSE: &str =
+    "Please limit requests to one every 5 seconds or contact kalev.leetaru5@gmail.com for larger queries.";
+
+// Average tone reported for the days the stub holds articles for
+pub const STUB_TONE: f64 = -2.5;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct StubArticle {
+    pub url: String,
+    pub url_mobile: String,
+    pub title: String,
+    pub seendate: String,
+    pub socialimage: String,
+    pub domain: String,
+    pub language: String,
+    pub sourcecountry: String,
+}
+
+impl StubArticle {
+    pub fn new(title: &str, seen_at: DateTime<Utc>, sourcecountry: &str) -> Self {
+        let slug = title.to_lowercase().replace(' ', "-");
+        Self {
+            url: format!("https://example.com/{}", slug),
+            url_mobile: format!("https://m.example.com/{}", slug),
+            title: title.to_string(),
+            seendate: seen_at.format("%Y%m%dT%H%M%SZ").to_string(),
+            socialimage: format!("https://example.com/images/{}.jpg", slug),
+            domain: "example.com".to_string(),
+            language: "French".to_string(),
+            sourcecountry: sourcecountry.to_string(),
+        }
+    }
+}
+
+// A stand in for the GDELT DOC api, answers artlist requests from a fixed set of articles
+// so the search client can be exercised without network access
+#[derive(Clone)]
+pub struct GDeltaStubServer {
+    state: Arc<StubState>,
+}
+
+struct StubState {
+    articles: Vec<StubArticle>,
+    // Number of upcoming requests answered with THROTTLE_RESPONSE
+    throttled_requests: Mutex<usize>,
+    requests: Mutex<Vec<HashMap<String, String>>>,
+}
+
+impl GDeltaStubServer {
+    pub fn new(articles: Vec<StubArticle>) -> Self {
+        Self {
+            state: Arc::new(StubState {
+                articles,
+                throttled_requests: Mutex::new(0),
+                requests: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    pub fn throttle_next_requests(&self, count: usize) {
+        *self.state.throttled_requests.lock().unwrap() = count;
+    }
+
+    // Query parameters of every request received so far
+    pub fn requests(&self) -> Vec<HashMap<String, String>> {
+        self.state.requests.lock().unwrap().clone()
+    }
+
+    // Serves the stub on a random local port, returns the base url to hand to the search client
+    pub fn start(&self) -> Result<(String, JoinHandle<()>), Box<dyn std::error::Error>> {
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))?;
+        let addr = listener.local_addr()?;
+        let app = Router::new()
+            .route("/api/v2/doc/doc", get(doc_handler))
+            .with_state(self.state.clone());
+        let server = axum::Server::from_tcp(listener)?.serve(app.into_make_service());
+        let handle = tokio::spawn(async move {
+            server.await.unwrap();
+        });
+        Ok((format!("http://{}", addr), handle))
+    }
+}
+
+async fn doc_handler(
+    State(state): State<Arc<StubState>>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Response {
+    state.requests.lock().unwrap().push(params.clone());
+    {
+        let mut throttled_requests = state.throttled_requests.lock().unwrap();
+        if *throttled_requests > 0 {
+            *throttled_requests -= 1;
+            return THROTTLE_RESPONSE.into_response();
+        }
+    }
+
+    let (start, end) = match (
+        params.get("startdatetime").and_then(|s| parse_datetime(s)),
+        params.get("enddatetime").and_then(|s| parse_datetime(s)),
+    ) {
+        (Some(start), Some(end)) => (start, end),
+        _ => return (StatusCode::BAD_REQUEST, "Invalid date range").into_response(),
+    };
+    let max_records = params
+        .get("maxrecords")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(75);
+
+    match params.get("mode").map(String::as_str) {
+        Some("artlist") => {
+            let mut articles: Vec<&StubArticle> = state
+                .articles
+                .iter()
+                .filter(|article| {
+                    parse_seendate(&article.seendate)
+                        .map(|seen_at| seen_at >= start && seen_at <= end)
+                        .unwrap_or(false)
+                })
+                .collect();
+            articles.sort_by(|a, b| b.seendate.cmp(&a.seendate));
+            articles.truncate(max_records);
+            if articles.is_empty() {
+                return Json(serde_json::json!({})).into_response();
+            }
+            Json(serde_json::json!({ "articles": articles })).into_response()
+        }
+        Some(mode @ ("timelinevol" | "timelinevolraw")) => Json(timeline(
+            &state.articles,
+            start,
+            end,
+            mode == "timelinevolraw",
+        ))
+        .into_response(),
+        Some("timelinetone") => Json(tone_timeline(&state.articles, start, end)).into_response(),
+        _ => (StatusCode::BAD_REQUEST, "Unsupported mode").into_response(),
+    }
+}
+
+// Daily article counts between start and end, the volume is the share of a pretend 1000
+// monitored articles per day
+fn timeline(
+    articles: &[StubArticle],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    raw: bool,
+) -> serde_json::Value {
+    let mut data = Vec::new();
+    let mut day = start.date_naive();
+    while day <= end.date_naive() {
+        let count = articles
+            .iter()
+            .filter_map(|article| parse_seendate(&article.seendate))
+            .filter(|seen_at| seen_at.date_naive() == day && *seen_at >= start && *seen_at <= end)
+            .count();
+        let date = day.format("%Y%m%dT000000Z").to_string();
+        data.push(if raw {
+            serde_json::json!({ "date": date, "value": count, "norm": 1000 })
+        } else {
+            serde_json::json!({ "date": date, "value": count as f64 / 10.0 })
+        });
+        day = day.succ_opt().unwrap();
+    }
+    serde_json::json!({ "timeline": [{ "series": "Volume", "data": data }] })
+}
+
+// Every day with articles gets STUB_TONE as its average tone
+fn tone_timeline(
+    articles: &[StubArticle],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> serde_json::Value {
+    let counts = timeline(articles, start, end, true);
+    let data: Vec<serde_json::Value> = counts["timeline"][0]["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|point| {
+            let tone = if point["value"].as_u64().unwrap_or(0) > 0 {
+                STUB_TONE
+            } else {
+                0.0
+            };
+            serde_json::json!({ "date": point["date"], "value": tone })
+        })
+        .collect();
+    serde_json::json!({ "timeline": [{ "series": "Average Tone", "data": data }] })
+}
+
+fn parse_datetime(s: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(s, "%Y%m%d%H%M%S")
+        .ok()
+        .map(|date| DateTime::<Utc>::from_utc(date, Utc))
+}
+
+fn parse_seendate(s: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(s, "%Y%m%dT%H%M%SZ")
+        .ok()
+        .map(|date| DateTime::<Utc>::from_utc(date, Utc))
+}
@@ -0,0 +1,824 @@
+// Each test crate uses its own part of these helpers
+#![allow(dead_code)]
+
+pub mod gdelt_stub;
+
+use async_trait::async_trait;
+use isocountry::CountryCode;
+use learn_rust::adapters::gdeltproject_scheduler::RequestSchedulerConfig;
+use learn_rust::adapters::news_search_client_gdeltproject::GDeltaProjectNewsSearchAdapter;
+use learn_rust::core::domain::{
+    ArticleCursor, ArticleFilter, ArticlePage, ArticleQuery, Category, CategoryKeyword,
+    CoverageMode, CoverageTimeline, DateRange, NewsArticle, PageRequest, QueuedQuery, SortOrder,
+    StoredArticles, SyncReport, SyncRunSummary, SyncStatus, SyncWatermark, ToneTimeline,
+};
+use learn_rust::core::ports::{Logger, NewsRepository, RepositoryError, SyncJobQueue, SyncLock};
+use learn_rust::core::service;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Clone)]
+pub struct NoopLogger;
+
+impl Logger for NoopLogger {
+    fn debug(&self, _msg: &str) {}
+    fn info(&self, _msg: &str) {}
+    fn warn(&self, _msg: &str) {}
+    fn error(&self, _msg: &str) {}
+    fn fatal(&self, msg: &str) {
+        panic!("{}", msg);
+    }
+    fn clone_box(&self) -> Box<dyn Logger> {
+        Box::new(self.clone())
+    }
+}
+
+pub fn fast_scheduler_config() -> RequestSchedulerConfig {
+    RequestSchedulerConfig {
+        min_interval: std::time::Duration::ZERO,
+        max_attempts: 3,
+        initial_backoff: std::time::Duration::from_millis(1),
+        max_backoff: std::time::Duration::from_millis(5),
+    }
+}
+
+pub fn build_service(
+    base_url: String,
+    repository: &InMemoryNewsRepository,
+) -> service::NewsService {
+    build_service_with_config(base_url, repository, fast_scheduler_config())
+}
+
+pub fn build_service_with_config(
+    base_url: String,
+    repository: &InMemoryNewsRepository,
+    scheduler_config: RequestSchedulerConfig,
+) -> service::NewsService {
+    let search_client =
+        GDeltaProjectNewsSearchAdapter::new(Box::new(NoopLogger), base_url, scheduler_config);
+    service::NewsService::new(
+        Box::new(NoopLogger),
+        Box::new(repository.clone()),
+        Arc::new(search_client),
+    )
+}
+
+// Keeps everything in memory, clones share the same storage so tests can inspect what the
+// service stored
+#[derive(Clone, Default)]
+pub struct InMemoryNewsRepository {
+    pub categories: Arc<Mutex<Vec<String>>>,
+    pub category_expressions: Arc<Mutex<HashMap<String, String>>>,
+    pub category_keywords: Arc<Mutex<Vec<CategoryKeyword>>>,
+    pub countries: Arc<Mutex<Vec<CountryCode>>>,
+    pub articles: Arc<Mutex<Vec<NewsArticle>>>,
+    pub coverage_timelines: Arc<Mutex<Vec<CoverageTimeline>>>,
+    pub tone_timelines: Arc<Mutex<Vec<ToneTimeline>>>,
+    pub sync_watermarks: Arc<Mutex<Vec<SyncWatermark>>>,
+    pub sync_reports: Arc<Mutex<Vec<SyncReport>>>,
+    // Makes category and article lookups fail as if the database was down
+    pub unavailable: Arc<Mutex<bool>>,
+}
+
+impl InMemoryNewsRepository {
+    pub fn new(categories: Vec<&str>, countries: Vec<CountryCode>) -> Self {
+        Self {
+            categories: Arc::new(Mutex::new(
+                categories.into_iter().map(String::from).collect(),
+            )),
+            category_expressions: Arc::new(Mutex::new(HashMap::new())),
+            category_keywords: Arc::new(Mutex::new(Vec::new())),
+            countries: Arc::new(Mutex::new(countries)),
+            articles: Arc::new(Mutex::new(Vec::new())),
+            coverage_timelines: Arc::new(Mutex::new(Vec::new())),
+            tone_timelines: Arc::new(Mutex::new(Vec::new())),
+            sync_watermarks: Arc::new(Mutex::new(Vec::new())),
+            sync_reports: Arc::new(Mutex::new(Vec::new())),
+            unavailable: Arc::new(Mutex::new(false)),
+        }
+    }
+
+    fn check_available(&self) -> Result<(), RepositoryError> {
+        if *self.unavailable.lock().unwrap() {
+            return Err(RepositoryError::Unavailable(
+                "connection refused".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn num_articles(&self) -> usize {
+        self.articles.lock().unwrap().len()
+    }
+}
+
+#[async_trait]
+impl NewsRepository for InMemoryNewsRepository {
+    async fn get_articles_by_categories(
+        &self,
+        categories: Vec<String>,
+        date_range: DateRange,
+        page: PageRequest,
+    ) -> Result<ArticlePage, RepositoryError> {
+        self.search_articles(categories, date_range, ArticleFilter::default(), page)
+            .await
+    }
+
+    async fn search_articles(
+        &self,
+        categories: Vec<String>,
+        date_range: DateRange,
+        filter: ArticleFilter,
+        page: PageRequest,
+    ) -> Result<ArticlePage, RepositoryError> {
+        self.check_available()?;
+        let articles = self.articles.lock().unwrap();
+        let key = |article: &NewsArticle| (article.datetime, article.id.unwrap());
+        let mut matching: Vec<&NewsArticle> = articles
+            .iter()
+            .filter(|article| {
+                article
+                    .categories
+                    .iter()
+                    .any(|category| categories.contains(category))
+                    && article.datetime >= date_range.inclusive_start_date
+                    && article.datetime <= date_range.inclusive_end_date
+                    && matches_filter(&filter, article)
+            })
+            .collect();
+        matching.sort_by_key(|article| key(article));
+        if page.order == SortOrder::Desc {
+            matching.reverse();
+        }
+        if let Some(cursor) = &page.after {
+            let cursor = (cursor.seen_at, cursor.id);
+            matching.retain(|article| match page.order {
+                SortOrder::Asc => key(article) > cursor,
+                SortOrder::Desc => key(article) < cursor,
+            });
+        }
+
+        let next_cursor = if matching.len() as i64 > page.limit {
+            matching.truncate(page.limit as usize);
+            matching.last().map(|article| ArticleCursor {
+                seen_at: article.datetime,
+                id: article.id.unwrap(),
+            })
+        } else {
+            None
+        };
+        let articles = matching
+            .into_iter()
+            .map(|article| {
+                let mut article = article.clone();
+                article.categories.sort();
+                article
+            })
+            .collect();
+        Ok(ArticlePage {
+            articles,
+            next_cursor,
+        })
+    }
+
+    // Like the unique constraint of the articles table, a duplicate only adds its categories
+    async fn store_articles(
+        &self,
+        articles: Vec<NewsArticle>,
+    ) -> Result<StoredArticles, RepositoryError> {
+        let mut stored = self.articles.lock().unwrap();
+        let mut result = StoredArticles::default();
+        for mut article in articles {
+            let existing = stored.iter_mut().find(|existing| {
+                existing.title == article.title
+                    && existing.domain == article.domain
+                    && existing.country == article.country
+                    && existing.datetime == article.datetime
+            });
+            match existing {
+                Some(existing) => {
+                    for category in article.categories {
+                        if !existing.categories.contains(&category) {
+                            existing.categories.push(category);
+                        }
+                    }
+                    result.duplicates += 1;
+                }
+                None => {
+                    let last_id = stored.iter().filter_map(|article| article.id).max();
+                    article.id = Some(last_id.unwrap_or(0) + 1);
+                    stored.push(article);
+                    result.inserted += 1;
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    async fn store_sync_report(&self, report: &SyncReport) -> Result<i32, RepositoryError> {
+        let mut reports = self.sync_reports.lock().unwrap();
+        let id = reports.len() as i32 + 1;
+        reports.push(SyncReport {
+            id: Some(id),
+            ..report.clone()
+        });
+        Ok(id)
+    }
+
+    async fn get_sync_runs(&self, limit: i64) -> Result<Vec<SyncRunSummary>, RepositoryError> {
+        Ok(self
+            .sync_reports
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .take(limit as usize)
+            .map(|report| SyncRunSummary::new(report.id.unwrap(), report))
+            .collect())
+    }
+
+    async fn get_sync_report(&self, id: i32) -> Result<Option<SyncReport>, RepositoryError> {
+        Ok(self
+            .sync_reports
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|report| report.id == Some(id))
+            .cloned())
+    }
+
+    async fn get_sync_statuses(&self) -> Result<Vec<SyncStatus>, RepositoryError> {
+        let mut statuses: Vec<SyncStatus> = Vec::new();
+        for report in self.sync_reports.lock().unwrap().iter() {
+            for query in report.queries.iter().filter(|query| query.is_complete()) {
+                match statuses.iter_mut().find(|status| {
+                    status.country == query.country && status.category == query.category
+                }) {
+                    Some(status) => status.last_success_at = Some(report.finished_at),
+                    None => statuses.push(SyncStatus {
+                        last_success_at: Some(report.finished_at),
+                        ..SyncStatus::new(query.country, query.category.clone())
+                    }),
+                }
+            }
+        }
+        for watermark in self.sync_watermarks.lock().unwrap().iter() {
+            match statuses.iter_mut().find(|status| {
+                status.country == watermark.country && status.category == watermark.category
+            }) {
+                Some(status) => status.synced_until = Some(watermark.synced_until),
+                None => statuses.push(SyncStatus {
+                    synced_until: Some(watermark.synced_until),
+                    ..SyncStatus::new(watermark.country, watermark.category.clone())
+                }),
+            }
+        }
+        Ok(statuses)
+    }
+
+    async fn add_category(
+        &self,
+        category: String,
+        expression: Option<String>,
+    ) -> Result<bool, RepositoryError> {
+        let mut categories = self.categories.lock().unwrap();
+        if categories.contains(&category) {
+            return Ok(false);
+        }
+        if let Some(expression) = expression {
+            self.category_expressions
+                .lock()
+                .unwrap()
+                .insert(category.clone(), expression);
+        }
+        categories.push(category);
+        Ok(true)
+    }
+
+    async fn get_category_expression(
+        &self,
+        category: String,
+    ) -> Result<Option<String>, RepositoryError> {
+        Ok(self
+            .category_expressions
+            .lock()
+            .unwrap()
+            .get(&category)
+            .cloned())
+    }
+
+    async fn get_article_titles(
+        &self,
+        date_range: DateRange,
+    ) -> Result<Vec<(i32, String)>, RepositoryError> {
+        Ok(self
+            .articles
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|article| {
+                article.datetime >= date_range.inclusive_start_date
+                    && article.datetime <= date_range.inclusive_end_date
+            })
+            .map(|article| (article.id.unwrap(), article.title.clone()))
+            .collect())
+    }
+
+    async fn add_article_categories(
+        &self,
+        category: String,
+        article_ids: Vec<i32>,
+    ) -> Result<i32, RepositoryError> {
+        let mut num_tagged = 0;
+        for article in self.articles.lock().unwrap().iter_mut() {
+            if article_ids.contains(&article.id.unwrap()) && !article.categories.contains(&category)
+            {
+                article.categories.push(category.clone());
+                num_tagged += 1;
+            }
+        }
+        Ok(num_tagged)
+    }
+
+    async fn is_valid_category(&self, category: String) -> Result<bool, RepositoryError> {
+        self.check_available()?;
+        Ok(self.categories.lock().unwrap().contains(&category))
+    }
+
+    async fn get_categories(&self) -> Result<Vec<String>, RepositoryError> {
+        Ok(self.categories.lock().unwrap().clone())
+    }
+
+    async fn get_category_definitions(&self) -> Result<Vec<Category>, RepositoryError> {
+        self.check_available()?;
+        let expressions = self.category_expressions.lock().unwrap();
+        let mut categories: Vec<Category> = self
+            .categories
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|name| Category {
+                name: name.clone(),
+                expression: expressions.get(name).cloned(),
+            })
+            .collect();
+        categories.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(categories)
+    }
+
+    async fn rename_category(
+        &self,
+        category: String,
+        new_name: String,
+    ) -> Result<Option<Category>, RepositoryError> {
+        let mut categories = self.categories.lock().unwrap();
+        if categories.contains(&new_name) {
+            return Err(RepositoryError::Conflict(format!(
+                "duplicate key value violates unique constraint: {}",
+                new_name
+            )));
+        }
+        let Some(position) = categories.iter().position(|name| *name == category) else {
+            return Ok(None);
+        };
+        categories[position] = new_name.clone();
+
+        let mut expressions = self.category_expressions.lock().unwrap();
+        if let Some(expression) = expressions.remove(&category) {
+            expressions.insert(new_name.clone(), expression);
+        }
+        for keyword in self.category_keywords.lock().unwrap().iter_mut() {
+            if keyword.category == category {
+                keyword.category = new_name.clone();
+            }
+        }
+        for article in self.articles.lock().unwrap().iter_mut() {
+            for tag in article.categories.iter_mut() {
+                if *tag == category {
+                    *tag = new_name.clone();
+                }
+            }
+        }
+        for watermark in self.sync_watermarks.lock().unwrap().iter_mut() {
+            if watermark.category == category {
+                watermark.category = new_name.clone();
+            }
+        }
+        Ok(Some(Category {
+            name: new_name.clone(),
+            expression: expressions.get(&new_name).cloned(),
+        }))
+    }
+
+    async fn delete_category(
+        &self,
+        category: String,
+        purge_articles: bool,
+    ) -> Result<Option<i32>, RepositoryError> {
+        let mut categories = self.categories.lock().unwrap();
+        let Some(position) = categories.iter().position(|name| *name == category) else {
+            return Ok(None);
+        };
+        categories.remove(position);
+        self.category_expressions.lock().unwrap().remove(&category);
+        self.category_keywords
+            .lock()
+            .unwrap()
+            .retain(|keyword| keyword.category != category);
+        self.sync_watermarks
+            .lock()
+            .unwrap()
+            .retain(|watermark| watermark.category != category);
+
+        // Articles left without a category are kept untagged unless purging
+        let mut articles = self.articles.lock().unwrap();
+        let before = articles.len();
+        articles.retain_mut(|article| {
+            let had_category = article.categories.contains(&category);
+            article.categories.retain(|tag| *tag != category);
+            !(purge_articles && had_category && article.categories.is_empty())
+        });
+        Ok(Some((before - articles.len()) as i32))
+    }
+
+    async fn add_category_keyword(
+        &self,
+        keyword: CategoryKeyword,
+    ) -> Result<bool, RepositoryError> {
+        let mut keywords = self.category_keywords.lock().unwrap();
+        if keywords.iter().any(|existing| {
+            existing.category == keyword.category && existing.phrase == keyword.phrase
+        }) {
+            return Ok(false);
+        }
+        keywords.push(keyword);
+        Ok(true)
+    }
+
+    async fn get_category_keywords(
+        &self,
+        category: String,
+    ) -> Result<Vec<CategoryKeyword>, RepositoryError> {
+        let mut keywords: Vec<CategoryKeyword> = self
+            .category_keywords
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|keyword| keyword.category == category)
+            .cloned()
+            .collect();
+        keywords.sort_by(|a, b| a.phrase.cmp(&b.phrase));
+        Ok(keywords)
+    }
+
+    async fn remove_category_keyword(
+        &self,
+        category: String,
+        phrase: String,
+    ) -> Result<bool, RepositoryError> {
+        let mut keywords = self.category_keywords.lock().unwrap();
+        let before = keywords.len();
+        keywords.retain(|keyword| !(keyword.category == category && keyword.phrase == phrase));
+        Ok(keywords.len() < before)
+    }
+
+    async fn get_countries(&self) -> Result<Vec<CountryCode>, RepositoryError> {
+        Ok(self.countries.lock().unwrap().clone())
+    }
+
+    async fn add_country(&self, country: CountryCode) -> Result<bool, RepositoryError> {
+        let mut countries = self.countries.lock().unwrap();
+        if countries.contains(&country) {
+            return Ok(false);
+        }
+        countries.push(country);
+        Ok(true)
+    }
+
+    async fn remove_country(
+        &self,
+        country: CountryCode,
+        purge_articles: bool,
+    ) -> Result<Option<i32>, RepositoryError> {
+        let mut countries = self.countries.lock().unwrap();
+        let Some(position) = countries.iter().position(|code| *code == country) else {
+            return Ok(None);
+        };
+        let mut articles = self.articles.lock().unwrap();
+        let num_articles = articles
+            .iter()
+            .filter(|article| article.country == country)
+            .count();
+        if num_articles > 0 && !purge_articles {
+            return Err(RepositoryError::Conflict(format!(
+                "{} is still referenced from news_articles",
+                country.alpha3()
+            )));
+        }
+        articles.retain(|article| article.country != country);
+        countries.remove(position);
+        self.sync_watermarks
+            .lock()
+            .unwrap()
+            .retain(|watermark| watermark.country != country);
+        Ok(Some(num_articles as i32))
+    }
+
+    async fn get_sync_watermark(
+        &self,
+        country: CountryCode,
+        category: String,
+    ) -> Result<Option<SyncWatermark>, RepositoryError> {
+        Ok(self
+            .sync_watermarks
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|watermark| watermark.country == country && watermark.category == category)
+            .cloned())
+    }
+
+    async fn set_sync_watermark(&self, watermark: &SyncWatermark) -> Result<(), RepositoryError> {
+        let mut watermarks = self.sync_watermarks.lock().unwrap();
+        match watermarks.iter_mut().find(|existing| {
+            existing.country == watermark.country && existing.category == watermark.category
+        }) {
+            Some(existing) => {
+                existing.synced_until = existing.synced_until.max(watermark.synced_until)
+            }
+            None => watermarks.push(watermark.clone()),
+        }
+        Ok(())
+    }
+
+    async fn store_coverage_timeline(
+        &self,
+        timeline: &CoverageTimeline,
+    ) -> Result<i32, RepositoryError> {
+        self.coverage_timelines
+            .lock()
+            .unwrap()
+            .push(CoverageTimeline {
+                country: timeline.country,
+                category: timeline.category.clone(),
+                mode: timeline.mode,
+                points: timeline.points.clone(),
+            });
+        Ok(timeline.points.len() as i32)
+    }
+
+    async fn get_coverage_timeline(
+        &self,
+        country: CountryCode,
+        category: String,
+        mode: CoverageMode,
+        date_range: DateRange,
+    ) -> Result<CoverageTimeline, RepositoryError> {
+        let points = self
+            .coverage_timelines
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|timeline| {
+                timeline.country == country
+                    && timeline.category == category
+                    && timeline.mode == mode
+            })
+            .flat_map(|timeline| timeline.points.clone())
+            .filter(|point| {
+                point.datetime >= date_range.inclusive_start_date
+                    && point.datetime <= date_range.inclusive_end_date
+            })
+            .collect();
+        Ok(CoverageTimeline {
+            country,
+            category,
+            mode,
+            points,
+        })
+    }
+
+    async fn store_tone_timeline(&self, timeline: &ToneTimeline) -> Result<i32, RepositoryError> {
+        self.tone_timelines.lock().unwrap().push(ToneTimeline {
+            country: timeline.country,
+            category: timeline.category.clone(),
+            points: timeline.points.clone(),
+        });
+        Ok(timeline.points.len() as i32)
+    }
+
+    async fn get_tone_timelines(
+        &self,
+        categories: Vec<String>,
+        date_range: DateRange,
+    ) -> Result<Vec<ToneTimeline>, RepositoryError> {
+        Ok(self
+            .tone_timelines
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|timeline| categories.contains(&timeline.category))
+            .map(|timeline| ToneTimeline {
+                country: timeline.country,
+                category: timeline.category.clone(),
+                points: timeline
+                    .points
+                    .iter()
+                    .filter(|point| {
+                        point.datetime >= date_range.inclusive_start_date
+                            && point.datetime <= date_range.inclusive_end_date
+                    })
+                    .cloned()
+                    .collect(),
+            })
+            .collect())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InMemorySyncJob {
+    pub id: i64,
+    pub country: CountryCode,
+    pub category: String,
+    pub date_range: DateRange,
+    pub status: String,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub worker_id: Option<String>,
+    pub lease_expires_at: Option<Instant>,
+    pub inserted: Option<i32>,
+    pub last_error: Option<String>,
+}
+
+impl InMemorySyncJob {
+    fn is_claimable(&self, now: Instant) -> bool {
+        let lease_expired = self.lease_expires_at.is_some_and(|expires| expires < now);
+        (self.status == "queued" || (self.status == "running" && lease_expired))
+            && self.attempts < self.max_attempts
+    }
+
+    fn is_held_by(&self, worker_id: &str) -> bool {
+        self.worker_id.as_deref() == Some(worker_id)
+    }
+}
+
+// Behaves like the sync_jobs table, clones share the same jobs
+#[derive(Clone, Default)]
+pub struct InMemorySyncJobQueue {
+    pub jobs: Arc<Mutex<Vec<InMemorySyncJob>>>,
+}
+
+impl InMemorySyncJobQueue {
+    pub fn jobs(&self) -> Vec<InMemorySyncJob> {
+        self.jobs.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl SyncJobQueue for InMemorySyncJobQueue {
+    async fn enqueue(
+        &self,
+        queries: Vec<ArticleQuery>,
+        max_attempts: i32,
+    ) -> Result<i32, RepositoryError> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let num_queued = queries.len() as i32;
+        for query in queries {
+            let id = jobs.len() as i64 + 1;
+            jobs.push(InMemorySyncJob {
+                id,
+                country: query.source_country,
+                category: query.category,
+                date_range: query.date_range,
+                status: "queued".to_string(),
+                attempts: 0,
+                max_attempts,
+                worker_id: None,
+                lease_expires_at: None,
+                inserted: None,
+                last_error: None,
+            });
+        }
+        Ok(num_queued)
+    }
+
+    async fn claim(
+        &self,
+        worker_id: &str,
+        lease: Duration,
+    ) -> Result<Option<QueuedQuery>, RepositoryError> {
+        let now = Instant::now();
+        let mut jobs = self.jobs.lock().unwrap();
+        let job = match jobs.iter_mut().find(|job| job.is_claimable(now)) {
+            Some(job) => job,
+            None => return Ok(None),
+        };
+        job.status = "running".to_string();
+        job.attempts += 1;
+        job.worker_id = Some(worker_id.to_string());
+        job.lease_expires_at = Some(now + lease);
+        Ok(Some(QueuedQuery {
+            id: job.id,
+            query: ArticleQuery::new(job.country, job.category.clone(), job.date_range.clone()),
+            attempts: job.attempts,
+            max_attempts: job.max_attempts,
+        }))
+    }
+
+    async fn heartbeat(
+        &self,
+        id: i64,
+        worker_id: &str,
+        lease: Duration,
+    ) -> Result<bool, RepositoryError> {
+        let mut jobs = self.jobs.lock().unwrap();
+        match jobs
+            .iter_mut()
+            .find(|job| job.id == id && job.is_held_by(worker_id) && job.status == "running")
+        {
+            Some(job) => {
+                job.lease_expires_at = Some(Instant::now() + lease);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn complete(
+        &self,
+        id: i64,
+        worker_id: &str,
+        inserted: i32,
+    ) -> Result<(), RepositoryError> {
+        let mut jobs = self.jobs.lock().unwrap();
+        if let Some(job) = jobs
+            .iter_mut()
+            .find(|job| job.id == id && job.is_held_by(worker_id))
+        {
+            job.status = "completed".to_string();
+            job.inserted = Some(inserted);
+            job.lease_expires_at = None;
+        }
+        Ok(())
+    }
+
+    async fn fail(&self, id: i64, worker_id: &str, error: &str) -> Result<(), RepositoryError> {
+        let mut jobs = self.jobs.lock().unwrap();
+        if let Some(job) = jobs
+            .iter_mut()
+            .find(|job| job.id == id && job.is_held_by(worker_id))
+        {
+            job.status = if job.attempts < job.max_attempts {
+                "queued"
+            } else {
+                "failed"
+            }
+            .to_string();
+            job.last_error = Some(error.to_string());
+            job.lease_expires_at = None;
+        }
+        Ok(())
+    }
+}
+
+// Lock shared by the clones, `held_elsewhere` pretends another process holds it
+#[derive(Clone, Default)]
+pub struct InMemorySyncLock {
+    pub locked: Arc<Mutex<bool>>,
+    pub held_elsewhere: Arc<Mutex<bool>>,
+    pub times_locked: Arc<Mutex<usize>>,
+}
+
+#[async_trait]
+impl SyncLock for InMemorySyncLock {
+    async fn try_lock(&self) -> Result<bool, RepositoryError> {
+        let mut locked = self.locked.lock().unwrap();
+        if *locked || *self.held_elsewhere.lock().unwrap() {
+            return Ok(false);
+        }
+        *locked = true;
+        *self.times_locked.lock().unwrap() += 1;
+        Ok(true)
+    }
+
+    async fn unlock(&self) -> Result<(), RepositoryError> {
+        *self.locked.lock().unwrap() = false;
+        Ok(())
+    }
+}
+
+// What the Postgres repository's query does with the filter, languages and domains are compared
+// ignoring case
+fn matches_filter(filter: &ArticleFilter, article: &NewsArticle) -> bool {
+    let any_ignoring_case =
+        |values: &[String], value: &str| values.iter().any(|v| v.eq_ignore_ascii_case(value));
+    (filter.countries.is_empty() || filter.countries.contains(&article.country))
+        && (filter.languages.is_empty() || any_ignoring_case(&filter.languages, &article.language))
+        && (filter.domains.is_empty() || any_ignoring_case(&filter.domains, &article.domain))
+        && !any_ignoring_case(&filter.excluded_domains, &article.domain)
+        && filter
+            .title_contains
+            .as_ref()
+            .is_none_or(|text| article.title.to_lowercase().contains(&text.to_lowercase()))
+}
@@ -0,0 +1,526 @@
+mod common;
+
+use chrono::{Duration, TimeZone, Utc};
+use common::gdelt_stub::{GDeltaStubServer, StubArticle, STUB_TONE};
+use common::{build_service, InMemoryNewsRepository};
+use isocountry::CountryCode;
+use learn_rust::core::domain::{
+    ArticleQuery, CategoryKeyword, CoverageMode, DateRange, PageRequest, SortOrder, SyncWatermark,
+};
+use learn_rust::core::ports::{NewsService, SearchClientError};
+use learn_rust::core::service;
+
+fn one_day() -> DateRange {
+    let start = Utc.with_ymd_and_hms(2023, 6, 1, 0, 0, 0).unwrap();
+    DateRange::new(start, start + Duration::days(1)).unwrap()
+}
+
+// Spreads the articles evenly over the day, a minute apart
+fn articles_over_one_day(count: usize) -> Vec<StubArticle> {
+    let start = one_day().inclusive_start_date;
+    (0..count)
+        .map(|i| {
+            StubArticle::new(
+                &format!("Article {}", i),
+                start + Duration::minutes(i as i64),
+                "France",
+            )
+        })
+        .collect()
+}
+
+fn climate_change_query() -> ArticleQuery {
+    ArticleQuery::new(CountryCode::FRA, "climate change".to_string(), one_day())
+}
+
+#[tokio::test]
+async fn test_fetch_and_store_articles_single_page() {
+    let stub = GDeltaStubServer::new(articles_over_one_day(100));
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec!["climate change"], vec![CountryCode::FRA]);
+    let news_service = build_service(base_url, &repository);
+
+    let count = news_service
+        .fetch_and_store_articles(climate_change_query())
+        .await
+        .unwrap();
+
+    assert_eq!(count, 100);
+    assert_eq!(repository.num_articles(), 100);
+    assert_eq!(stub.requests().len(), 1);
+    let articles = repository.articles.lock().unwrap();
+    assert_eq!(
+        articles[0].url_mobile,
+        Some(articles[0].url.replace("https://", "https://m."))
+    );
+    assert!(articles[0].social_image.is_some());
+}
+
+#[tokio::test]
+async fn test_fetch_and_store_articles_splits_saturated_pages() {
+    let stub = GDeltaStubServer::new(articles_over_one_day(600));
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec!["climate change"], vec![CountryCode::FRA]);
+    let news_service = build_service(base_url, &repository);
+
+    let count = news_service
+        .fetch_and_store_articles(climate_change_query())
+        .await
+        .unwrap();
+
+    assert_eq!(count, 600);
+    assert_eq!(repository.num_articles(), 600);
+    assert!(stub.requests().len() > 1);
+}
+
+#[tokio::test]
+async fn test_fetch_and_store_articles_retries_throttled_requests() {
+    let stub = GDeltaStubServer::new(articles_over_one_day(100));
+    stub.throttle_next_requests(2);
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec!["climate change"], vec![CountryCode::FRA]);
+    let news_service = build_service(base_url, &repository);
+
+    news_service
+        .fetch_and_store_articles(climate_change_query())
+        .await
+        .unwrap();
+
+    assert_eq!(repository.num_articles(), 100);
+    assert_eq!(stub.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn test_fetch_and_store_articles_gives_up_after_max_attempts() {
+    let stub = GDeltaStubServer::new(articles_over_one_day(100));
+    stub.throttle_next_requests(10);
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec!["climate change"], vec![CountryCode::FRA]);
+    let news_service = build_service(base_url, &repository);
+
+    news_service
+        .fetch_and_store_articles(climate_change_query())
+        .await
+        .unwrap();
+
+    assert_eq!(repository.num_articles(), 0);
+    assert_eq!(stub.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn test_fetch_and_store_articles_keeps_what_it_can_from_unsplittable_windows() {
+    // More articles than a single page share the same timestamp, so no split can separate them
+    let seen_at = one_day().inclusive_start_date + Duration::hours(3);
+    let articles = (0..300)
+        .map(|i| StubArticle::new(&format!("Article {}", i), seen_at, "France"))
+        .collect();
+    let stub = GDeltaStubServer::new(articles);
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec!["climate change"], vec![CountryCode::FRA]);
+    let news_service = build_service(base_url, &repository);
+
+    news_service
+        .fetch_and_store_articles(climate_change_query())
+        .await
+        .unwrap();
+
+    assert_eq!(repository.num_articles(), 250);
+}
+
+#[tokio::test]
+async fn test_fetch_and_store_articles_rejects_unknown_category() {
+    let stub = GDeltaStubServer::new(articles_over_one_day(10));
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec!["environment"], vec![CountryCode::FRA]);
+    let news_service = build_service(base_url, &repository);
+
+    let result = news_service
+        .fetch_and_store_articles(climate_change_query())
+        .await;
+
+    assert!(result.is_err());
+    assert!(stub.requests().is_empty());
+}
+
+#[tokio::test]
+async fn test_fetch_and_store_articles_searches_category_keywords() {
+    let stub = GDeltaStubServer::new(articles_over_one_day(10));
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec!["climate change"], vec![CountryCode::FRA]);
+    let news_service = build_service(base_url, &repository);
+    for (phrase, language) in [
+        ("global warming", None),
+        ("changement climatique", Some("French")),
+    ] {
+        let keyword = CategoryKeyword::new(
+            "climate change".to_string(),
+            phrase.to_string(),
+            language.map(String::from),
+        );
+        assert!(news_service.add_category_keyword(keyword).await.unwrap());
+    }
+
+    let count = news_service
+        .fetch_and_store_articles(climate_change_query())
+        .await
+        .unwrap();
+
+    assert_eq!(count, 10);
+    assert_eq!(
+        stub.requests()[0]["query"],
+        "sourcecountry:FR AND (\"climate change\" OR \"changement climatique\" OR \"global warming\")"
+    );
+    let articles = repository.articles.lock().unwrap();
+    assert!(articles
+        .iter()
+        .all(|article| article.categories == vec!["climate change".to_string()]));
+}
+
+#[tokio::test]
+async fn test_add_category_keyword_rejects_unknown_category() {
+    let repository = InMemoryNewsRepository::new(vec!["climate change"], vec![CountryCode::FRA]);
+    let news_service = build_service(String::from("http://127.0.0.1:1"), &repository);
+
+    let result = news_service
+        .add_category_keyword(CategoryKeyword::new(
+            "sports".to_string(),
+            "football".to_string(),
+            None,
+        ))
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(service::NewsServiceError::InvalidCategory(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_fetch_and_store_articles_searches_category_expression() {
+    let stub = GDeltaStubServer::new(articles_over_one_day(10));
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec![], vec![CountryCode::FRA]);
+    let news_service = build_service(base_url, &repository);
+    news_service
+        .add_category(
+            "heat wave".to_string(),
+            Some("\"heat wave\" AND NOT sport".to_string()),
+        )
+        .await
+        .unwrap();
+
+    let query = ArticleQuery::new(CountryCode::FRA, "heat wave".to_string(), one_day());
+    let count = news_service.fetch_and_store_articles(query).await.unwrap();
+
+    assert_eq!(count, 10);
+    assert_eq!(
+        stub.requests()[0]["query"],
+        "sourcecountry:FR AND \"heat wave\" -\"sport\""
+    );
+}
+
+#[tokio::test]
+async fn test_add_category_rejects_invalid_expression() {
+    let repository = InMemoryNewsRepository::new(vec![], vec![CountryCode::FRA]);
+    let news_service = build_service(String::from("http://127.0.0.1:1"), &repository);
+
+    let result = news_service
+        .add_category(
+            "heat wave".to_string(),
+            Some("\"heat wave\" AND".to_string()),
+        )
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(service::NewsServiceError::InvalidExpression(_))
+    ));
+    assert!(repository.categories.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_add_category_rejects_expression_gdelt_can_not_search() {
+    let repository = InMemoryNewsRepository::new(vec![], vec![CountryCode::FRA]);
+    let news_service = build_service(String::from("http://127.0.0.1:1"), &repository);
+
+    for expression in [
+        "(drought AND heat) OR wildfire",
+        "NOT (sport OR football) AND heat",
+    ] {
+        let result = news_service
+            .add_category("heat wave".to_string(), Some(expression.to_string()))
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(service::NewsServiceError::UnsupportedExpression(_))
+        ));
+    }
+    assert!(repository.categories.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_add_category_keyword_rejects_keyword_on_and_expression() {
+    let repository = InMemoryNewsRepository::new(vec![], vec![CountryCode::FRA]);
+    let news_service = build_service(String::from("http://127.0.0.1:1"), &repository);
+    news_service
+        .add_category(
+            "heat wave".to_string(),
+            Some("\"heat wave\" AND NOT sport".to_string()),
+        )
+        .await
+        .unwrap();
+
+    let result = news_service
+        .add_category_keyword(CategoryKeyword::new(
+            "heat wave".to_string(),
+            "canicule".to_string(),
+            Some("French".to_string()),
+        ))
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(service::NewsServiceError::UnsupportedExpression(_))
+    ));
+    assert!(repository.category_keywords.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_reclassify_articles() {
+    let stub = GDeltaStubServer::new(vec![
+        StubArticle::new(
+            "Heat wave hits Paris",
+            one_day().inclusive_start_date,
+            "France",
+        ),
+        StubArticle::new(
+            "Heat wave cancels sport events",
+            one_day().inclusive_start_date,
+            "France",
+        ),
+        StubArticle::new(
+            "Glaciers are melting",
+            one_day().inclusive_start_date,
+            "France",
+        ),
+    ]);
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec!["climate change"], vec![CountryCode::FRA]);
+    let news_service = build_service(base_url, &repository);
+    news_service
+        .fetch_and_store_articles(climate_change_query())
+        .await
+        .unwrap();
+    news_service
+        .add_category(
+            "heat wave".to_string(),
+            Some("\"heat wave\" AND NOT sport".to_string()),
+        )
+        .await
+        .unwrap();
+
+    let tagged = news_service
+        .reclassify_articles("heat wave".to_string(), one_day())
+        .await
+        .unwrap();
+
+    assert_eq!(tagged, 1);
+    let page = news_service
+        .get_articles_by_categories(
+            vec!["heat wave".to_string()],
+            one_day(),
+            PageRequest {
+                limit: 10,
+                order: SortOrder::Desc,
+                after: None,
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(page.articles.len(), 1);
+    assert_eq!(page.articles[0].title, "Heat wave hits Paris");
+}
+
+#[tokio::test]
+async fn test_sync_articles_isolates_failing_queries() {
+    let stub = GDeltaStubServer::new(articles_over_one_day(10));
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(
+        vec!["climate change", "drought", "heat wave", "flood"],
+        vec![CountryCode::FRA],
+    );
+    // Skips the validation of add_category, syncing this category fails
+    repository
+        .category_expressions
+        .lock()
+        .unwrap()
+        .insert("flood".to_string(), "flood AND".to_string());
+    let news_service = build_service(base_url, &repository).with_sync_concurrency(2);
+
+    let report = news_service.sync_articles(one_day()).await.unwrap();
+
+    // The stub doesn't filter on the query, the other categories find the same 10 articles
+    assert_eq!(report.queries.len(), 4);
+    assert_eq!(report.fetched(), 30);
+    assert_eq!(report.inserted(), 10);
+    assert_eq!(report.duplicates(), 20);
+    assert_eq!(stub.requests().len(), 3);
+    let failed: Vec<_> = report.failed_queries().collect();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].category, "flood");
+    assert!(failed[0].errors[0].contains("Invalid category expression"));
+    assert_eq!(report.id, Some(1));
+    assert_eq!(repository.sync_reports.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_sync_articles_incrementally_starts_at_watermark() {
+    let now = Utc::now();
+    let stub = GDeltaStubServer::new(vec![
+        StubArticle::new("Old article", now - Duration::days(2), "France"),
+        StubArticle::new("New article", now - Duration::minutes(30), "France"),
+    ]);
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec!["climate change"], vec![CountryCode::FRA]);
+    let watermark = now - Duration::hours(1);
+    repository
+        .sync_watermarks
+        .lock()
+        .unwrap()
+        .push(SyncWatermark {
+            country: CountryCode::FRA,
+            category: "climate change".to_string(),
+            synced_until: watermark,
+        });
+    let news_service = build_service(base_url, &repository);
+
+    let report = news_service
+        .sync_articles_incrementally(now - Duration::days(60))
+        .await
+        .unwrap();
+
+    let start = watermark - Duration::minutes(service::INDEXING_LAG_MINUTES);
+    assert_eq!(report.inserted(), 1);
+    assert_eq!(report.queries[0].inclusive_start_date, start);
+    assert_eq!(
+        stub.requests()[0]["startdatetime"],
+        start.format("%Y%m%d%H%M%S").to_string()
+    );
+    let watermarks = repository.sync_watermarks.lock().unwrap();
+    assert!(watermarks[0].synced_until >= now);
+}
+
+#[tokio::test]
+async fn test_sync_articles_incrementally_picks_up_late_indexed_articles() {
+    let now = Utc::now();
+    let watermark = now - Duration::hours(1);
+    // Seen before the previous sync ended but only indexed by GDELT afterwards
+    let stub = GDeltaStubServer::new(vec![StubArticle::new(
+        "Late article",
+        watermark - Duration::minutes(10),
+        "France",
+    )]);
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec!["climate change"], vec![CountryCode::FRA]);
+    repository
+        .sync_watermarks
+        .lock()
+        .unwrap()
+        .push(SyncWatermark {
+            country: CountryCode::FRA,
+            category: "climate change".to_string(),
+            synced_until: watermark,
+        });
+    let news_service = build_service(base_url, &repository);
+
+    let report = news_service
+        .sync_articles_incrementally(now - Duration::days(60))
+        .await
+        .unwrap();
+
+    assert_eq!(report.inserted(), 1);
+    assert_eq!(repository.articles.lock().unwrap()[0].title, "Late article");
+}
+
+#[tokio::test]
+async fn test_sync_articles_incrementally_keeps_watermark_on_failure() {
+    let now = Utc::now();
+    let stub = GDeltaStubServer::new(vec![StubArticle::new(
+        "New article",
+        now - Duration::minutes(30),
+        "France",
+    )]);
+    stub.throttle_next_requests(usize::MAX);
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec!["climate change"], vec![CountryCode::FRA]);
+    let news_service = build_service(base_url, &repository);
+
+    let report = news_service
+        .sync_articles_incrementally(now - Duration::days(1))
+        .await
+        .unwrap();
+
+    assert_eq!(report.inserted(), 0);
+    assert_eq!(report.failed_windows(), 1);
+    assert!(repository.sync_watermarks.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_sync_coverage_timeline() {
+    let stub = GDeltaStubServer::new(articles_over_one_day(100));
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec!["climate change"], vec![CountryCode::FRA]);
+    let news_service = build_service(base_url, &repository);
+
+    let timeline = news_service
+        .sync_coverage_timeline(climate_change_query(), CoverageMode::VolumeRaw)
+        .await
+        .unwrap();
+
+    assert_eq!(timeline.mode, CoverageMode::VolumeRaw);
+    assert_eq!(timeline.points.len(), 2);
+    assert_eq!(timeline.points[0].value, 100.0);
+    assert_eq!(timeline.points[0].total, Some(1000));
+    assert_eq!(stub.requests()[0]["mode"], "timelinevolraw");
+}
+
+#[tokio::test]
+async fn test_sync_coverage_timeline_reports_throttling() {
+    let stub = GDeltaStubServer::new(articles_over_one_day(100));
+    stub.throttle_next_requests(usize::MAX);
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec!["climate change"], vec![CountryCode::FRA]);
+    let news_service = build_service(base_url, &repository);
+
+    let result = news_service
+        .sync_coverage_timeline(climate_change_query(), CoverageMode::VolumeRaw)
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(service::NewsServiceError::SearchClientError(
+            SearchClientError::Throttled(_)
+        ))
+    ));
+    assert!(repository.coverage_timelines.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_sync_tone_timelines() {
+    let stub = GDeltaStubServer::new(articles_over_one_day(10));
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec!["climate change"], vec![CountryCode::FRA]);
+    let news_service = build_service(base_url, &repository);
+
+    let num_points = news_service.sync_tone_timelines(one_day()).await.unwrap();
+    assert_eq!(num_points, 2);
+
+    let timelines = news_service
+        .get_tone_timelines(vec!["climate change".to_string()], one_day())
+        .await
+        .unwrap();
+    assert_eq!(timelines.len(), 1);
+    assert_eq!(timelines[0].points[0].average_tone, STUB_TONE);
+    assert_eq!(stub.requests()[0]["mode"], "timelinetone");
+}
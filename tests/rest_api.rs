@@ -0,0 +1,634 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Method, Request, StatusCode};
+use axum::Router;
+use chrono::{Duration, TimeZone, Utc};
+use common::gdelt_stub::{GDeltaStubServer, StubArticle};
+use common::{
+    build_service, build_service_with_config, fast_scheduler_config, InMemoryNewsRepository,
+    NoopLogger,
+};
+use isocountry::CountryCode;
+use learn_rust::adapters::gdeltproject_scheduler::RequestSchedulerConfig;
+use learn_rust::core::domain::DateRange;
+use learn_rust::core::ports::NewsService;
+use learn_rust::handlers::rest::RestHandler;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+fn one_day() -> DateRange {
+    let start = Utc.with_ymd_and_hms(2023, 6, 1, 0, 0, 0).unwrap();
+    DateRange::new(start, start + Duration::days(1)).unwrap()
+}
+
+// Syncs a day of articles for two categories through the stub, then serves the api on top of
+// the resulting repository
+async fn synced_router() -> Router {
+    let start = one_day().inclusive_start_date;
+    let stub = GDeltaStubServer::new(vec![
+        StubArticle::new("Heat wave hits Paris", start, "France"),
+        StubArticle::new("Glaciers are melting", start + Duration::hours(1), "France"),
+    ]);
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(
+        vec!["climate change", "heat wave"],
+        vec![CountryCode::FRA, CountryCode::DEU],
+    );
+    let news_service = Arc::new(build_service(base_url, &repository));
+    news_service.sync_articles(one_day()).await.unwrap();
+    RestHandler::new(news_service, Box::new(NoopLogger), "0".to_string()).router()
+}
+
+async fn get(router: Router, uri: &str) -> (StatusCode, serde_json::Value) {
+    send(router, Method::GET, uri, None).await
+}
+
+async fn send(
+    router: Router,
+    method: Method,
+    uri: &str,
+    body: Option<serde_json::Value>,
+) -> (StatusCode, serde_json::Value) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json");
+    let body = match body {
+        Some(body) => Body::from(body.to_string()),
+        None => Body::empty(),
+    };
+    let response = router.oneshot(request.body(body).unwrap()).await.unwrap();
+    let status = response.status();
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let json = serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null);
+    (status, json)
+}
+
+#[tokio::test]
+async fn test_get_sync_runs() {
+    let router = synced_router().await;
+
+    let (status, runs) = get(router, "/sync-runs?limit=5").await;
+
+    assert_eq!(status, StatusCode::OK);
+    let runs = runs.as_array().unwrap();
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0]["id"], 1);
+    assert_eq!(runs[0]["mode"], "full");
+    // Both categories find the same two French articles, Germany finds none
+    assert_eq!(runs[0]["fetched"], 4);
+    assert_eq!(runs[0]["inserted"], 2);
+    assert_eq!(runs[0]["failed_queries"], 0);
+}
+
+#[tokio::test]
+async fn test_get_sync_run() {
+    let router = synced_router().await;
+
+    let (status, run) = get(router.clone(), "/sync-runs/1").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(run["queries"].as_array().unwrap().len(), 4);
+
+    let (status, _) = get(router, "/sync-runs/42").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_get_sync_status() {
+    let router = synced_router().await;
+
+    let (status, statuses) = get(router, "/sync-status").await;
+
+    assert_eq!(status, StatusCode::OK);
+    let statuses = statuses.as_array().unwrap();
+    assert_eq!(statuses.len(), 4);
+    let france = statuses
+        .iter()
+        .find(|status| status["country"] == "FR" && status["category"] == "heat wave")
+        .unwrap();
+    assert!(france["last_success_at"].is_string());
+    assert!(france["staleness_seconds"].as_i64().unwrap() >= 0);
+}
+
+fn sync_job_body(categories: Vec<&str>) -> serde_json::Value {
+    serde_json::json!({
+        "countries": ["FR", "DEU"],
+        "categories": categories,
+        "inclusive_start_date": "2023-06-01",
+        "inclusive_end_date": "2023-06-02",
+    })
+}
+
+// Polls the job until it finished, giving up after a few seconds
+async fn wait_for_job(router: Router, id: i64) -> serde_json::Value {
+    for _ in 0..100 {
+        let (_, job) = get(router.clone(), &format!("/jobs/{}", id)).await;
+        if job["status"] != "queued" && job["status"] != "running" {
+            return job;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    panic!("Sync job {} did not finish", id);
+}
+
+#[tokio::test]
+async fn test_sync_job() {
+    let start = one_day().inclusive_start_date;
+    let stub = GDeltaStubServer::new(vec![StubArticle::new(
+        "Heat wave hits Paris",
+        start,
+        "France",
+    )]);
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec!["heat wave"], vec![]);
+    let news_service = Arc::new(build_service(base_url, &repository));
+    let router = RestHandler::new(news_service, Box::new(NoopLogger), "0".to_string()).router();
+
+    let (status, created) = send(
+        router.clone(),
+        Method::POST,
+        "/sync",
+        Some(sync_job_body(vec!["heat wave"])),
+    )
+    .await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+
+    let job = wait_for_job(router, created["job_id"].as_i64().unwrap()).await;
+    assert_eq!(job["status"], "completed");
+    assert_eq!(job["total_queries"], 2);
+    assert_eq!(job["completed_queries"], 2);
+    assert_eq!(job["inserted"], 1);
+    assert_eq!(repository.num_articles(), 1);
+}
+
+#[tokio::test]
+async fn test_sync_job_rejects_invalid_requests() {
+    let repository = InMemoryNewsRepository::new(vec!["heat wave"], vec![]);
+    let news_service = Arc::new(build_service("http://127.0.0.1:1".to_string(), &repository));
+    let router = RestHandler::new(news_service, Box::new(NoopLogger), "0".to_string()).router();
+
+    let (status, _) = send(
+        router.clone(),
+        Method::POST,
+        "/sync",
+        Some(sync_job_body(vec!["sports"])),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let mut body = sync_job_body(vec!["heat wave"]);
+    body["countries"] = serde_json::json!(["Atlantis"]);
+    let (status, _) = send(router.clone(), Method::POST, "/sync", Some(body)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = get(router, "/jobs/1").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_cancel_sync_job() {
+    let stub = GDeltaStubServer::new(vec![]);
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec!["heat wave"], vec![]);
+    // The second query has to wait a minute for its turn, long enough to cancel the job
+    let scheduler_config = RequestSchedulerConfig {
+        min_interval: std::time::Duration::from_secs(60),
+        ..fast_scheduler_config()
+    };
+    let news_service = Arc::new(build_service_with_config(
+        base_url,
+        &repository,
+        scheduler_config,
+    ));
+    let router = RestHandler::new(news_service, Box::new(NoopLogger), "0".to_string()).router();
+    let (_, created) = send(
+        router.clone(),
+        Method::POST,
+        "/sync",
+        Some(sync_job_body(vec!["heat wave"])),
+    )
+    .await;
+    let uri = format!("/jobs/{}", created["job_id"]);
+
+    let (status, job) = send(router.clone(), Method::DELETE, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(job["status"], "cancelled");
+
+    let (_, job) = get(router, &uri).await;
+    assert_eq!(job["status"], "cancelled");
+    assert!(job["completed_queries"].as_i64().unwrap() < 2);
+}
+
+#[tokio::test]
+async fn test_cancel_sync_job_stops_the_searches() {
+    let stub = GDeltaStubServer::new(vec![]);
+    stub.throttle_next_requests(usize::MAX);
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec!["heat wave"], vec![]);
+    // Keeps retrying the throttled requests for as long as the searches run
+    let scheduler_config = RequestSchedulerConfig {
+        max_attempts: u32::MAX,
+        initial_backoff: std::time::Duration::from_millis(10),
+        max_backoff: std::time::Duration::from_millis(10),
+        ..fast_scheduler_config()
+    };
+    let news_service = Arc::new(build_service_with_config(
+        base_url,
+        &repository,
+        scheduler_config,
+    ));
+    let router = RestHandler::new(news_service, Box::new(NoopLogger), "0".to_string()).router();
+    let (_, created) = send(
+        router.clone(),
+        Method::POST,
+        "/sync",
+        Some(sync_job_body(vec!["heat wave"])),
+    )
+    .await;
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    let uri = format!("/jobs/{}", created["job_id"]);
+
+    let (status, _) = send(router, Method::DELETE, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let num_requests = stub.requests().len();
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+    assert!(num_requests > 0);
+    assert_eq!(stub.requests().len(), num_requests);
+}
+
+#[tokio::test]
+async fn test_get_articles_by_category() {
+    let router = synced_router().await;
+
+    let (status, body) = get(
+        router,
+        "/get-articles-by-category?categories=climate%20change,heat%20wave&inclusive_start_date=2023-06-01&inclusive_end_date=2023-06-02",
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    // Each article once, with every category it was found under
+    let articles = body["articles"].as_array().unwrap();
+    assert_eq!(articles.len(), 2);
+    for article in articles {
+        assert!(article["id"].is_i64());
+        assert_eq!(
+            article["categories"],
+            serde_json::json!(["climate change", "heat wave"])
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_errors_are_problem_details() {
+    let router = synced_router().await;
+
+    let (status, problem) = get(
+        router.clone(),
+        "/get-articles-by-category?categories=heat%20wave&inclusive_start_date=2023-06-02&inclusive_end_date=2023-06-01",
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(problem["status"], 400);
+    assert_eq!(problem["title"], "Invalid date range");
+
+    let (status, problem) = get(
+        router.clone(),
+        "/get-articles-by-category?categories=heat%20wave&inclusive_start_date=June&inclusive_end_date=2023-06-01",
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(problem["title"], "Invalid request");
+
+    let (status, problem) = get(
+        router.clone(),
+        "/get-articles-by-category?categories=sports&inclusive_start_date=2023-06-01&inclusive_end_date=2023-06-02",
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(problem["title"], "Unknown category");
+    assert!(problem["detail"].as_str().unwrap().contains("sports"));
+
+    let (status, problem) = get(router, "/sync-runs/42").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(problem["status"], 404);
+}
+
+#[tokio::test]
+async fn test_database_outages_are_unavailable() {
+    let repository = InMemoryNewsRepository::new(vec!["heat wave"], vec![CountryCode::FRA]);
+    let news_service = Arc::new(build_service("http://127.0.0.1:1".to_string(), &repository));
+    let router = RestHandler::new(news_service, Box::new(NoopLogger), "0".to_string()).router();
+    *repository.unavailable.lock().unwrap() = true;
+
+    let (status, problem) = get(router.clone(), "/is-valid-category/heat%20wave").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(problem["status"], 503);
+    // The cause stays in the logs
+    assert!(!problem["detail"]
+        .as_str()
+        .unwrap()
+        .contains("connection refused"));
+
+    let (status, _) = get(
+        router,
+        "/get-articles-by-category?categories=heat%20wave&inclusive_start_date=2023-06-01&inclusive_end_date=2023-06-02",
+    )
+    .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn test_manage_categories() {
+    let router = synced_router().await;
+
+    let (status, categories) = get(router.clone(), "/categories").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        categories,
+        serde_json::json!([
+            {"name": "climate change", "expression": null},
+            {"name": "heat wave", "expression": null},
+        ])
+    );
+
+    let drought = serde_json::json!({"name": " drought ", "expression": "drought AND NOT sport"});
+    let (status, created) = send(
+        router.clone(),
+        Method::POST,
+        "/categories",
+        Some(drought.clone()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(created["name"], "drought");
+    assert_eq!(created["expression"], "drought AND NOT sport");
+    let (status, _) = send(router.clone(), Method::POST, "/categories", Some(drought)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    for invalid in [
+        serde_json::json!({"name": " "}),
+        serde_json::json!({"name": "floods", "expression": "(floods"}),
+        serde_json::json!({"expression": "floods"}),
+    ] {
+        let (status, _) = send(router.clone(), Method::POST, "/categories", Some(invalid)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    let (status, renamed) = send(
+        router.clone(),
+        Method::PATCH,
+        "/categories/climate%20change",
+        Some(serde_json::json!({"name": "climat"})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(renamed["name"], "climat");
+    // The articles follow the category
+    let (_, response) = get(
+        router.clone(),
+        "/get-articles-by-category?categories=climat&inclusive_start_date=2023-06-01&inclusive_end_date=2023-06-02",
+    )
+    .await;
+    assert_eq!(response["articles"].as_array().unwrap().len(), 2);
+
+    let (status, _) = send(
+        router.clone(),
+        Method::PATCH,
+        "/categories/climat",
+        Some(serde_json::json!({"name": "heat wave"})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, problem) = send(
+        router,
+        Method::PATCH,
+        "/categories/climate%20change",
+        Some(serde_json::json!({"name": "climate"})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(problem["title"], "Unknown category");
+}
+
+#[tokio::test]
+async fn test_delete_category() {
+    let router = synced_router().await;
+
+    // The articles are also about climate change, they are kept even when purging
+    let (status, deleted) = send(
+        router.clone(),
+        Method::DELETE,
+        "/categories/heat%20wave?purge_articles=true",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(deleted["deleted_articles"], 0);
+
+    let (status, deleted) = send(
+        router.clone(),
+        Method::DELETE,
+        "/categories/climate%20change?purge_articles=true",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(deleted["deleted_articles"], 2);
+
+    let (_, categories) = get(router.clone(), "/categories").await;
+    assert_eq!(categories, serde_json::json!([]));
+    let (status, _) = send(router, Method::DELETE, "/categories/heat%20wave", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_manage_countries() {
+    let router = synced_router().await;
+
+    let (status, countries) = get(router.clone(), "/countries").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        countries,
+        serde_json::json!([
+            {"alpha_2": "DE", "alpha_3": "DEU", "name": "Germany"},
+            {"alpha_2": "FR", "alpha_3": "FRA", "name": "France"},
+        ])
+    );
+
+    let (status, added) = send(
+        router.clone(),
+        Method::POST,
+        "/countries",
+        Some(serde_json::json!({"country": "spain"})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(added["alpha_3"], "ESP");
+    assert!(added.get("backfill_job_id").is_none());
+    let (status, _) = send(
+        router.clone(),
+        Method::POST,
+        "/countries",
+        Some(serde_json::json!({"country": "ES"})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, _) = send(
+        router.clone(),
+        Method::POST,
+        "/countries",
+        Some(serde_json::json!({"country": "Atlantis"})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // France has articles, they have to be purged along with it
+    let (status, _) = send(router.clone(), Method::DELETE, "/countries/FR", None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, removed) = send(
+        router.clone(),
+        Method::DELETE,
+        "/countries/FR?purge_articles=true",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(removed["deleted_articles"], 2);
+    let (status, removed) = send(router.clone(), Method::DELETE, "/countries/esp", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(removed["deleted_articles"], 0);
+
+    let (_, countries) = get(router.clone(), "/countries").await;
+    assert_eq!(countries.as_array().unwrap().len(), 1);
+    let (status, _) = send(router, Method::DELETE, "/countries/France", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_add_country_with_backfill() {
+    let stub = GDeltaStubServer::new(vec![StubArticle::new(
+        "Heat wave hits Paris",
+        Utc::now() - Duration::hours(12),
+        "France",
+    )]);
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec!["heat wave"], vec![]);
+    let news_service = Arc::new(build_service(base_url, &repository));
+    let router = RestHandler::new(news_service, Box::new(NoopLogger), "0".to_string()).router();
+
+    let backfill_from = (Utc::now() - Duration::days(2))
+        .format("%Y-%m-%d")
+        .to_string();
+    let (status, added) = send(
+        router.clone(),
+        Method::POST,
+        "/countries",
+        Some(serde_json::json!({"country": "FRA", "backfill_from": backfill_from})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let job = wait_for_job(router, added["backfill_job_id"].as_i64().unwrap()).await;
+    assert_eq!(job["status"], "completed");
+    assert_eq!(job["inserted"], 1);
+    assert_eq!(
+        *repository.countries.lock().unwrap(),
+        vec![CountryCode::FRA]
+    );
+}
+
+#[tokio::test]
+async fn test_add_country_with_failed_backfill() {
+    let repository = InMemoryNewsRepository::new(vec!["heat wave"], vec![]);
+    let news_service = Arc::new(build_service("http://127.0.0.1:9".to_string(), &repository));
+    let router = RestHandler::new(news_service, Box::new(NoopLogger), "0".to_string()).router();
+    // Countries can still be added, but the categories of the backfill can't be looked up
+    *repository.unavailable.lock().unwrap() = true;
+
+    let backfill_from = (Utc::now() - Duration::days(2))
+        .format("%Y-%m-%d")
+        .to_string();
+    let body = serde_json::json!({"country": "FRA", "backfill_from": backfill_from});
+    let (status, added) = send(
+        router.clone(),
+        Method::POST,
+        "/countries",
+        Some(body.clone()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(added["alpha_3"], "FRA");
+    assert!(added.get("backfill_job_id").is_none());
+    assert!(added["backfill_error"].is_string());
+    assert_eq!(
+        *repository.countries.lock().unwrap(),
+        vec![CountryCode::FRA]
+    );
+
+    let (status, _) = send(router, Method::POST, "/countries", Some(body)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn test_get_articles_page_by_page() {
+    let router = synced_router().await;
+    let uri = "/get-articles-by-category?categories=climate%20change&inclusive_start_date=2023-06-01&inclusive_end_date=2023-06-02&limit=1";
+
+    let (status, first) = get(router.clone(), &format!("{}&sort=asc", uri)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(first["articles"].as_array().unwrap().len(), 1);
+    assert_eq!(first["articles"][0]["title"], "Heat wave hits Paris");
+    let cursor = first["next_cursor"].as_str().unwrap();
+
+    let (_, second) = get(
+        router.clone(),
+        &format!("{}&sort=asc&cursor={}", uri, cursor),
+    )
+    .await;
+    assert_eq!(second["articles"][0]["title"], "Glaciers are melting");
+    assert!(second["next_cursor"].is_null());
+
+    // Newest first by default
+    let (_, newest) = get(router.clone(), uri).await;
+    assert_eq!(newest["articles"][0]["title"], "Glaciers are melting");
+
+    let (status, problem) = get(router, &format!("{}&cursor=nonsense", uri)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(problem["title"], "Invalid request");
+}
+
+#[tokio::test]
+async fn test_filter_articles() {
+    let router = synced_router().await;
+    let uri = "/get-articles-by-category?categories=climate%20change&inclusive_start_date=2023-06-01&inclusive_end_date=2023-06-02";
+
+    for (filter, expected) in [
+        ("title_contains=GLACIER", 1),
+        ("languages=english,french", 2),
+        ("languages=english", 0),
+        ("countries=fr", 2),
+        ("countries=Germany", 0),
+        ("domains=Example.com", 2),
+        ("exclude_domains=example.com", 0),
+        (
+            "countries=FRA&title_contains=paris&exclude_domains=spam.com",
+            1,
+        ),
+    ] {
+        let (status, response) = get(router.clone(), &format!("{}&{}", uri, filter)).await;
+        assert_eq!(status, StatusCode::OK, "{}", filter);
+        assert_eq!(
+            response["articles"].as_array().unwrap().len(),
+            expected,
+            "{}",
+            filter
+        );
+    }
+
+    let (status, problem) = get(router, &format!("{}&countries=Atlantis", uri)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(problem["detail"].as_str().unwrap().contains("Atlantis"));
+}
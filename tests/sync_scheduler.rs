@@ -0,0 +1,87 @@
+mod common;
+
+use chrono::{Duration, Utc};
+use common::gdelt_stub::{GDeltaStubServer, StubArticle};
+use common::{build_service, InMemoryNewsRepository, InMemorySyncLock, NoopLogger};
+use isocountry::CountryCode;
+use learn_rust::core::scheduler::{SyncSchedule, SyncScheduler};
+use learn_rust::core::service::NewsService;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// Articles from the last few hours so incremental syncs starting a day back find them
+fn recent_articles(count: i64) -> Vec<StubArticle> {
+    let start = Utc::now() - Duration::hours(6);
+    (0..count)
+        .map(|i| {
+            StubArticle::new(
+                &format!("Article {}", i),
+                start + Duration::minutes(i),
+                "France",
+            )
+        })
+        .collect()
+}
+
+fn build_scheduler(news_service: NewsService, lock: &InMemorySyncLock) -> SyncScheduler {
+    SyncScheduler::new(
+        Box::new(NoopLogger),
+        Arc::new(news_service),
+        Arc::new(lock.clone()),
+        SyncSchedule::parse("1h").unwrap(),
+        Duration::days(1),
+    )
+}
+
+#[tokio::test]
+async fn test_scheduled_sync_holds_the_lock() {
+    let stub = GDeltaStubServer::new(recent_articles(50));
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec!["climate change"], vec![CountryCode::FRA]);
+    let lock = InMemorySyncLock::default();
+    let scheduler = build_scheduler(build_service(base_url, &repository), &lock);
+
+    let report = scheduler.run_once().await.unwrap().unwrap();
+
+    assert_eq!(report.inserted(), 50);
+    assert_eq!(repository.num_articles(), 50);
+    assert_eq!(*lock.times_locked.lock().unwrap(), 1);
+    assert!(!*lock.locked.lock().unwrap());
+}
+
+#[tokio::test]
+async fn test_scheduled_sync_skips_while_another_sync_runs() {
+    let stub = GDeltaStubServer::new(recent_articles(50));
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec!["climate change"], vec![CountryCode::FRA]);
+    let lock = InMemorySyncLock::default();
+    *lock.held_elsewhere.lock().unwrap() = true;
+    let scheduler = build_scheduler(build_service(base_url, &repository), &lock);
+
+    let report = scheduler.run_once().await.unwrap();
+
+    assert!(report.is_none());
+    assert_eq!(repository.num_articles(), 0);
+    assert!(stub.requests().is_empty());
+}
+
+#[tokio::test]
+async fn test_stopped_scheduler_starts_no_queries() {
+    let stub = GDeltaStubServer::new(recent_articles(50));
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec!["climate change"], vec![CountryCode::FRA]);
+    let lock = InMemorySyncLock::default();
+    let stop = Arc::new(AtomicBool::new(false));
+    let news_service = build_service(base_url, &repository).with_stop_signal(stop.clone());
+    let scheduler = build_scheduler(news_service, &lock);
+
+    stop.store(true, Ordering::SeqCst);
+    let report = scheduler.run_once().await.unwrap().unwrap();
+    assert!(report.queries.is_empty());
+
+    tokio::time::timeout(std::time::Duration::from_secs(5), scheduler.run(stop))
+        .await
+        .unwrap();
+    assert_eq!(*lock.times_locked.lock().unwrap(), 1);
+    assert!(stub.requests().is_empty());
+}
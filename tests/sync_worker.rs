@@ -0,0 +1,182 @@
+mod common;
+
+use chrono::{Duration, TimeZone, Utc};
+use common::gdelt_stub::{GDeltaStubServer, StubArticle};
+use common::{build_service, InMemoryNewsRepository, InMemorySyncJobQueue, NoopLogger};
+use isocountry::CountryCode;
+use learn_rust::core::domain::{ArticleQuery, DateRange};
+use learn_rust::core::ports::SyncJobQueue;
+use learn_rust::core::sync_worker::{enqueue_backfill, SyncWorker, SyncWorkerConfig};
+use std::sync::Arc;
+
+fn one_day() -> DateRange {
+    let start = Utc.with_ymd_and_hms(2023, 6, 1, 0, 0, 0).unwrap();
+    DateRange::new(start, start + Duration::days(1)).unwrap()
+}
+
+// An article every 15 minutes of the day
+fn articles_over_one_day() -> Vec<StubArticle> {
+    let start = one_day().inclusive_start_date;
+    (0..96)
+        .map(|i| {
+            StubArticle::new(
+                &format!("Article {}", i),
+                start + Duration::minutes(15 * i),
+                "France",
+            )
+        })
+        .collect()
+}
+
+fn build_worker(
+    base_url: String,
+    repository: &InMemoryNewsRepository,
+    queue: &InMemorySyncJobQueue,
+    worker_id: &str,
+) -> SyncWorker {
+    SyncWorker::new(
+        Box::new(NoopLogger),
+        Arc::new(build_service(base_url, repository)),
+        Arc::new(queue.clone()),
+        worker_id.to_string(),
+        SyncWorkerConfig {
+            lease: std::time::Duration::from_secs(60),
+            heartbeat_interval: std::time::Duration::from_millis(10),
+            poll_interval: std::time::Duration::from_millis(10),
+        },
+    )
+}
+
+async fn run_until_empty(worker: &SyncWorker) {
+    while worker.run_once().await.unwrap() {}
+}
+
+#[tokio::test]
+async fn test_workers_share_a_backfill() {
+    let stub = GDeltaStubServer::new(articles_over_one_day());
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec!["climate change"], vec![CountryCode::FRA]);
+    let queue = InMemorySyncJobQueue::default();
+
+    let queued = enqueue_backfill(
+        &queue,
+        vec!["climate change".to_string()],
+        vec![CountryCode::FRA],
+        one_day(),
+        Duration::hours(6),
+        3,
+    )
+    .await
+    .unwrap();
+    assert_eq!(queued, 4);
+
+    let first = build_worker(base_url.clone(), &repository, &queue, "first");
+    let second = build_worker(base_url, &repository, &queue, "second");
+    tokio::join!(run_until_empty(&first), run_until_empty(&second));
+
+    let jobs = queue.jobs();
+    assert!(jobs.iter().all(|job| job.status == "completed"));
+    assert_eq!(
+        jobs.iter().map(|job| job.inserted.unwrap()).sum::<i32>(),
+        96
+    );
+    assert_eq!(repository.num_articles(), 96);
+}
+
+#[tokio::test]
+async fn test_failed_jobs_are_retried_until_out_of_attempts() {
+    let stub = GDeltaStubServer::new(articles_over_one_day());
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec!["climate change"], vec![CountryCode::FRA]);
+    let queue = InMemorySyncJobQueue::default();
+    queue
+        .enqueue(
+            vec![ArticleQuery::new(
+                CountryCode::FRA,
+                "unknown".to_string(),
+                one_day(),
+            )],
+            2,
+        )
+        .await
+        .unwrap();
+
+    let worker = build_worker(base_url, &repository, &queue, "worker");
+    assert!(worker.run_once().await.unwrap());
+    assert_eq!(queue.jobs()[0].status, "queued");
+    assert!(worker.run_once().await.unwrap());
+    assert!(!worker.run_once().await.unwrap());
+
+    let job = &queue.jobs()[0];
+    assert_eq!(job.status, "failed");
+    assert_eq!(job.attempts, 2);
+    assert!(job.last_error.as_ref().unwrap().contains("unknown"));
+}
+
+#[tokio::test]
+async fn test_jobs_with_failed_windows_are_retried() {
+    let stub = GDeltaStubServer::new(articles_over_one_day());
+    // More than the search client retries, the first attempt of the job gives up on its window
+    stub.throttle_next_requests(3);
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec!["climate change"], vec![CountryCode::FRA]);
+    let queue = InMemorySyncJobQueue::default();
+    queue
+        .enqueue(
+            vec![ArticleQuery::new(
+                CountryCode::FRA,
+                "climate change".to_string(),
+                one_day(),
+            )],
+            2,
+        )
+        .await
+        .unwrap();
+
+    let worker = build_worker(base_url, &repository, &queue, "worker");
+    assert!(worker.run_once().await.unwrap());
+    let job = &queue.jobs()[0];
+    assert_eq!(job.status, "queued");
+    assert!(job.last_error.as_ref().unwrap().contains("Gave up"));
+
+    assert!(worker.run_once().await.unwrap());
+    let job = &queue.jobs()[0];
+    assert_eq!(job.status, "completed");
+    assert_eq!(job.attempts, 2);
+    assert_eq!(repository.num_articles(), 96);
+}
+
+#[tokio::test]
+async fn test_jobs_of_crashed_workers_are_claimed_again() {
+    let stub = GDeltaStubServer::new(articles_over_one_day());
+    let (base_url, _server) = stub.start().unwrap();
+    let repository = InMemoryNewsRepository::new(vec!["climate change"], vec![CountryCode::FRA]);
+    let queue = InMemorySyncJobQueue::default();
+    queue
+        .enqueue(
+            vec![ArticleQuery::new(
+                CountryCode::FRA,
+                "climate change".to_string(),
+                one_day(),
+            )],
+            3,
+        )
+        .await
+        .unwrap();
+
+    let worker = build_worker(base_url, &repository, &queue, "worker");
+    // A worker claims the job and stops without ever renewing its lease
+    let lease = std::time::Duration::from_millis(200);
+    assert!(queue.claim("crashed", lease).await.unwrap().is_some());
+    assert!(!worker.run_once().await.unwrap());
+
+    tokio::time::sleep(lease * 2).await;
+    assert!(!queue.heartbeat(1, "worker", lease).await.unwrap());
+    assert!(worker.run_once().await.unwrap());
+
+    let job = &queue.jobs()[0];
+    assert_eq!(job.status, "completed");
+    assert_eq!(job.attempts, 2);
+    assert_eq!(job.worker_id.as_deref(), Some("worker"));
+    assert_eq!(repository.num_articles(), 96);
+}